@@ -168,3 +168,20 @@ test('provide events', async (t) => {
 
   t.is(events.length, 4)
 })
+
+test('delete tagged blob', async (t) => {
+  const node = await Iroh.memory()
+
+  const bytes = Array.from(Buffer.from('hello'))
+  const res = await node.blobs.addBytes(bytes)
+  await node.blobs.addBytesNamed(bytes, 'named')
+
+  const tags = await node.blobs.tagsFor(res.hash)
+  t.is(tags.length, 2)
+
+  // tagged blobs are only deleted when forced
+  await t.throwsAsync(node.blobs.deleteBlob(res.hash, false))
+  const removed = await node.blobs.deleteBlob(res.hash, true)
+  t.is(removed.length, 2)
+  t.deepEqual(await node.blobs.tagsFor(res.hash), [])
+})
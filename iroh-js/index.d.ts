@@ -163,8 +163,22 @@ export declare class Blobs {
    * `tags_to_delete` on those tags, and they will be deleted once the collection is created.
    */
   createCollection(collection: Collection, tag: SetTagOption, tagsToDelete: Array<string>): Promise<HashAndTag>
-  /** Delete a blob. */
-  deleteBlob(hash: string): Promise<void>
+  /**
+   * Get the names of all tags that reference the blob directly.
+   *
+   * This lists all tags of the node, so it takes time proportional to the number of tags.
+   */
+  tagsFor(hash: string): Promise<Array<Array<number>>>
+  /**
+   * Delete a blob.
+   *
+   * If the blob is referenced by any tags, this fails listing those tags, unless `force`
+   * is set. With `force`, all tags referencing the blob are removed before it is deleted.
+   *
+   * Returns the names of the removed tags. Like [`Self::tags_for`], this lists all tags of
+   * the node to find the ones referencing the blob.
+   */
+  deleteBlob(hash: string, force: boolean): Promise<Array<Array<number>>>
 }
 
 /**
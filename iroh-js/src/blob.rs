@@ -26,6 +26,23 @@ impl Blobs {
     fn client(&self) -> &iroh::client::Iroh {
         self.node.inner_client()
     }
+
+    /// Find all tags that reference `hash`.
+    ///
+    /// The nodes of these bindings do not keep an index of their tags, so this lists all tags
+    /// of the node.
+    async fn tags_referencing(
+        &self,
+        hash: iroh::blobs::Hash,
+    ) -> anyhow::Result<Vec<iroh::blobs::Tag>> {
+        self.client()
+            .tags()
+            .list()
+            .await?
+            .try_filter_map(|tag| async move { Ok((tag.hash == hash).then_some(tag.name)) })
+            .try_collect()
+            .await
+    }
 }
 
 #[napi]
@@ -325,26 +342,46 @@ impl Blobs {
         })
     }
 
-    /// Delete a blob.
+    /// Get the names of all tags that reference the blob directly.
+    ///
+    /// This lists all tags of the node, so it takes time proportional to the number of tags.
     #[napi]
-    pub async fn delete_blob(&self, hash: String) -> Result<()> {
-        let mut tags = self.client().tags().list().await?;
+    pub async fn tags_for(&self, hash: String) -> Result<Vec<Vec<u8>>> {
         let hash: iroh::blobs::Hash = hash.parse().map_err(anyhow::Error::from)?;
+        let tags = self.tags_referencing(hash).await?;
+        Ok(tags.into_iter().map(|tag| tag.0.to_vec()).collect())
+    }
 
-        let mut name = None;
-        while let Some(tag) = tags.next().await {
-            let tag = tag?;
-            if tag.hash == hash {
-                name = Some(tag.name);
-            }
+    /// Delete a blob.
+    ///
+    /// If the blob is referenced by any tags, this fails listing those tags, unless `force`
+    /// is set. With `force`, all tags referencing the blob are removed before it is deleted.
+    ///
+    /// Returns the names of the removed tags. Like [`Self::tags_for`], this lists all tags of
+    /// the node to find the ones referencing the blob.
+    #[napi]
+    pub async fn delete_blob(&self, hash: String, force: bool) -> Result<Vec<Vec<u8>>> {
+        let hash: iroh::blobs::Hash = hash.parse().map_err(anyhow::Error::from)?;
+        let tags = self.tags_referencing(hash).await?;
+        if !tags.is_empty() && !force {
+            let names = tags
+                .iter()
+                .map(|tag| tag.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(
+                anyhow::anyhow!("blob is referenced by {} tag(s): {}", tags.len(), names).into(),
+            );
         }
 
-        if let Some(name) = name {
-            self.client().tags().delete(name).await?;
-            self.client().blobs().delete_blob(hash).await?;
+        let mut removed = Vec::with_capacity(tags.len());
+        for tag in tags {
+            self.client().tags().delete(tag.clone()).await?;
+            removed.push(tag.0.to_vec());
         }
+        self.client().blobs().delete_blob(hash).await?;
 
-        Ok(())
+        Ok(removed)
     }
 }
 
@@ -284,7 +284,7 @@ runBlocking {
     val removeHash = hashes.removeAt(0)
     val removeTag = tags.removeAt(0)
     // delete the tag for the first blob
-    node.tags().delete(removeTag)
+    node.tags().delete(Tag.fromBytes(removeTag))
     // wait for GC to clear the blob
     java.lang.Thread.sleep(500)
 
@@ -36,7 +36,7 @@ runBlocking {
     // Join the same doc from node_1
     val ticket = doc0.share(ShareMode.WRITE, AddrInfoOptions.RELAY_AND_ADDRESSES)
     val cb1 = Subscriber()
-    val doc1 = node1.docs().joinAndSubscribe(ticket, cb1)
+    val doc1 = node1.docs().joinAndSubscribe(ticket, cb1).doc
 
     // wait for initial sync
     while (true) {
@@ -7,7 +7,7 @@ import time
 import iroh
 import asyncio
 
-from iroh import Hash, Iroh, SetTagOption, BlobFormat, WrapOption, AddProgressType, NodeOptions
+from iroh import Hash, Iroh, SetTagOption, Tag, BlobFormat, WrapOption, AddProgressType, NodeOptions
 
 def test_hash():
     hash_str = "2kbxxbofqx5rau77wzafrj4yntjb4gn4olfpwxmv26js6dvhgjhq"
@@ -244,7 +244,7 @@ async def test_list_and_delete():
     remove_hash = hashes.pop(0)
     remove_tag = tags.pop(0)
     # delete the tag for the first blob
-    await node.tags().delete(remove_tag)
+    await node.tags().delete(Tag.from_bytes(remove_tag))
     # wait for GC to clear the blob
     time.sleep(0.5)
 
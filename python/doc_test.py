@@ -159,7 +159,7 @@ async def test_doc_import_export():
     #
     # export entry
     path = key_to_path(key, None, out_root)
-    await doc.export_file(entry, path, None)
+    await doc.export_file(entry, path, iroh.BlobExportMode.COPY, None)
     #
     # read file
     file = open(path, "rb")
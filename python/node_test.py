@@ -42,7 +42,8 @@ async def test_basic_sync():
     # Join the same doc from node_1
     found_s_1 = asyncio.Queue(maxsize=1)
     cb1 = SubscribeCallback(found_s_1)
-    doc_1 = await node_1.docs().join_and_subscribe(ticket, cb1)
+    joined = await node_1.docs().join_and_subscribe(ticket, cb1)
+    doc_1 = joined.doc
 
     # wait for initial sync
     while (True):
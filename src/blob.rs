@@ -1,14 +1,27 @@
 use std::{
+    collections::{HashMap, HashSet},
     path::PathBuf,
     str::FromStr,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
     time::Duration,
 };
 
-use futures::{StreamExt, TryStreamExt};
+use bao_tree::{io::BaoContentItem, BaoTree, ChunkNum, ChunkRanges};
+use futures::{
+    future::{BoxFuture, Shared},
+    stream::BoxStream,
+    FutureExt, Stream, StreamExt, TryStreamExt,
+};
+use iroh::blobs::store::{BaoBatchWriter, MapEntry, MapEntryMut, Store};
 use serde::{Deserialize, Serialize};
+use tokio::{io::AsyncReadExt, sync::Mutex};
+use tokio_util::sync::CancellationToken;
 
-use crate::{node::Iroh, CallbackError};
+use crate::store::{FsLayout, Location, TagIndex};
+use crate::{node::Iroh, CallbackError, DocExportProgress};
 use crate::{ticket::AddrInfoOptions, BlobTicket};
 use crate::{IrohError, NodeAddr};
 
@@ -30,6 +43,149 @@ impl Blobs {
     fn client(&self) -> &iroh::client::Iroh {
         self.node.inner_client()
     }
+
+    /// Start a download, returning a stream of its progress events.
+    async fn download_stream(
+        &self,
+        hash: iroh::blobs::Hash,
+        opts: &BlobDownloadOptions,
+    ) -> anyhow::Result<BoxStream<'static, anyhow::Result<iroh::blobs::get::db::DownloadProgress>>>
+    {
+        if opts.ranges.is_none() && !opts.skip_complete {
+            let stream = self
+                .client()
+                .blobs()
+                .download_with_opts(hash, opts.opts.clone())
+                .await?;
+            return Ok(StreamExt::boxed(stream));
+        }
+
+        let ranges = opts.ranges.clone();
+        let (sender, receiver) = flume::bounded(32);
+        let opts = opts.opts.clone();
+        match &self.node {
+            Iroh::Fs(node, store, _) => {
+                let store = store.clone();
+                let endpoint = node.endpoint().clone();
+                node.local_pool_handle().spawn_detached(move || {
+                    download_ranges(store, endpoint, hash, opts, ranges, sender)
+                });
+            }
+            Iroh::Memory(node, store, _) => {
+                let store = store.clone();
+                let endpoint = node.endpoint().clone();
+                node.local_pool_handle().spawn_detached(move || {
+                    download_ranges(store, endpoint, hash, opts, ranges, sender)
+                });
+            }
+            Iroh::Client(_) => {
+                anyhow::bail!("ranged and incremental downloads are not supported for RPC clients");
+            }
+        }
+        Ok(receiver.into_stream().boxed())
+    }
+
+    /// Load a collection, replacing nested collection entries by their contents.
+    ///
+    /// The names of the returned entries are paths relative to the root collection. The
+    /// entries of each collection come before the contents of its nested collections.
+    /// Fails if the tree exceeds [`MAX_TREE_DEPTH`] or [`MAX_TREE_ENTRIES`].
+    async fn collection_tree(
+        &self,
+        hash: iroh::blobs::Hash,
+    ) -> anyhow::Result<iroh::blobs::format::collection::Collection> {
+        let mut tree = iroh::blobs::format::collection::Collection::default();
+        // a collection can be nested at several paths, only load it once
+        let mut loaded = HashMap::new();
+        let mut entries = 0;
+        let mut stack = vec![(String::new(), hash, 0)];
+        while let Some((prefix, hash, depth)) = stack.pop() {
+            let collection = match loaded.get(&hash) {
+                Some(collection) => Arc::clone(collection),
+                None => {
+                    let collection = Arc::new(self.client().blobs().get_collection(hash).await?);
+                    loaded.insert(hash, collection.clone());
+                    collection
+                }
+            };
+            entries += collection.len();
+            check_tree_limits(depth, entries)?;
+            let mut nested = Vec::new();
+            for (name, hash) in collection.iter() {
+                match nested_collection_name(name) {
+                    Some(dir) => nested.push((format!("{prefix}{dir}/"), *hash, depth + 1)),
+                    None => tree.push(format!("{prefix}{name}"), *hash),
+                }
+            }
+            // visit nested collections in order
+            stack.extend(nested.into_iter().rev());
+        }
+        Ok(tree)
+    }
+
+    /// Find all tags that reference `hash`.
+    ///
+    /// Nodes spawned by this library look the tags up in their index, RPC clients have to
+    /// list all tags of the node.
+    async fn tags_referencing(
+        &self,
+        hash: iroh::blobs::Hash,
+    ) -> anyhow::Result<Vec<iroh::blobs::Tag>> {
+        if let Iroh::Fs(_, _, state) | Iroh::Memory(_, _, state) = &self.node {
+            return Ok(state.tags.tags_for(&hash).await);
+        }
+        self.client()
+            .tags()
+            .list()
+            .await?
+            .try_filter_map(|tag| async move { Ok((tag.hash == hash).then_some(tag.name)) })
+            .try_collect()
+            .await
+    }
+
+    /// The name of the automatic tag of a queued download.
+    ///
+    /// Every request for the download, including those of [`DownloadHandle::add_providers`],
+    /// sets this one tag, instead of each creating an automatic tag of its own. The tag is
+    /// only set once the download completes, so the counter keeps downloads started within
+    /// the same millisecond apart.
+    async fn queued_download_tag(&self) -> anyhow::Result<iroh::blobs::Tag> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let index = self.tag_index().await?;
+        loop {
+            let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let tag = iroh::blobs::Tag::auto(std::time::SystemTime::now(), |_| false);
+            let tag = iroh::blobs::Tag::from(format!("{}-{n}", String::from_utf8_lossy(&tag.0)));
+            if index.get(&tag).await.is_none() {
+                return Ok(tag);
+            }
+        }
+    }
+
+    /// The index of the tags of the node, by the hash they reference.
+    ///
+    /// Nodes spawned by this library keep an index up to date, for RPC clients a snapshot is
+    /// built by listing all tags of the node.
+    async fn tag_index(&self) -> anyhow::Result<Arc<TagIndex>> {
+        if let Iroh::Fs(_, _, state) | Iroh::Memory(_, _, state) = &self.node {
+            return Ok(state.tags.clone());
+        }
+        let tags: Vec<_> = self
+            .client()
+            .tags()
+            .list()
+            .await?
+            .map_ok(|tag| {
+                (
+                    tag.name,
+                    iroh::blobs::HashAndFormat::new(tag.hash, tag.format),
+                )
+            })
+            .try_collect()
+            .await?;
+        Ok(Arc::new(TagIndex::snapshot(tags)))
+    }
 }
 
 #[uniffi::export]
@@ -37,7 +193,7 @@ impl Blobs {
     /// List all complete blobs.
     ///
     /// Note: this allocates for each `BlobListResponse`, if you have many `BlobListReponse`s this may be a prohibitively large list.
-    /// Please file an [issue](https://github.com/n0-computer/iroh-ffi/issues/new) if you run into this issue
+    /// Use [`Self::list_iter`] to enumerate the blobs in batches instead.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn list(&self) -> Result<Vec<Arc<Hash>>, IrohError> {
         let response = self.client().blobs().list().await?;
@@ -50,6 +206,55 @@ impl Blobs {
         Ok(hashes)
     }
 
+    /// Iterate over all complete blobs.
+    ///
+    /// Blobs are fetched lazily, in batches, via [`BlobListIterator::next_batch`].
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn list_iter(&self) -> Result<Arc<BlobListIterator>, IrohError> {
+        let stream = self
+            .client()
+            .blobs()
+            .list()
+            .await?
+            .map_ok(|i| Arc::new(Hash(i.hash)));
+        Ok(Arc::new(BlobListIterator(BatchStream::new(stream))))
+    }
+
+    /// List all complete blobs, with their size, location and the tags referencing them.
+    ///
+    /// Note: this allocates for each blob, if you have many blobs this may be a prohibitively
+    /// large list. Use [`Self::list_info_iter`] to enumerate the blobs in batches instead.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn list_info(&self) -> Result<Vec<BlobInfo>, IrohError> {
+        let tags = self.tag_index().await?;
+        let blobs = self
+            .client()
+            .blobs()
+            .list()
+            .await?
+            .and_then(|info| BlobInfo::new(info, tags.clone()))
+            .try_collect()
+            .await?;
+        Ok(blobs)
+    }
+
+    /// Iterate over all complete blobs, with their size, location and the tags referencing them.
+    ///
+    /// Blobs are fetched lazily, in batches, via [`BlobInfoListIterator::next_batch`], and their
+    /// tags are looked up as they are returned. RPC clients list all tags of the node once,
+    /// when the iterator is created.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn list_info_iter(&self) -> Result<Arc<BlobInfoListIterator>, IrohError> {
+        let tags = self.tag_index().await?;
+        let stream = self
+            .client()
+            .blobs()
+            .list()
+            .await?
+            .and_then(move |info| BlobInfo::new(info, tags.clone()));
+        Ok(Arc::new(BlobInfoListIterator(BatchStream::new(stream))))
+    }
+
     /// Get the size information on a single blob.
     ///
     /// Method only exists in FFI
@@ -59,11 +264,32 @@ impl Blobs {
         Ok(r.size())
     }
 
+    /// Open a streaming reader for a single blob.
+    ///
+    /// Unlike [`Self::read_to_bytes`], this does not buffer the whole blob, so it can be
+    /// used to read blobs of any size chunk by chunk.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn open_reader(&self, hash: Arc<Hash>) -> Result<Arc<BlobReader>, IrohError> {
+        let reader = self.client().blobs().read(hash.0).await?;
+        self.node.record_access(hash.0);
+        Ok(Arc::new(BlobReader {
+            node: self.node.clone(),
+            hash: hash.0,
+            size: reader.size(),
+            is_complete: reader.is_complete(),
+            state: Mutex::new(BlobReaderState {
+                reader,
+                position: 0,
+            }),
+        }))
+    }
+
     /// Read all bytes of single blob.
     ///
     /// This allocates a buffer for the full blob. Use only if you know that the blob you're
     /// reading is small. If not sure, use [`Self::blobs_size`] and check the size with
-    /// before calling [`Self::blobs_read_to_bytes`].
+    /// before calling [`Self::blobs_read_to_bytes`], or stream the content using
+    /// [`Self::open_reader`].
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn read_to_bytes(&self, hash: Arc<Hash>) -> Result<Vec<u8>, IrohError> {
         let res = self
@@ -72,6 +298,7 @@ impl Blobs {
             .read_to_bytes(hash.0)
             .await
             .map(|b| b.to_vec())?;
+        self.node.record_access(hash.0);
         Ok(res)
     }
 
@@ -79,7 +306,8 @@ impl Blobs {
     ///
     /// This allocates a buffer for the full length `len`. Use only if you know that the blob you're
     /// reading is small. If not sure, use [`Self::blobs_size`] and check the size with
-    /// before calling [`Self::blobs_read_at_to_bytes`].
+    /// before calling [`Self::blobs_read_at_to_bytes`], or stream the content using
+    /// [`Self::open_reader`].
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn read_at_to_bytes(
         &self,
@@ -93,6 +321,7 @@ impl Blobs {
             .read_at_to_bytes(hash.0, offset, (*len).into())
             .await
             .map(|b| b.to_vec())?;
+        self.node.record_access(hash.0);
         Ok(res)
     }
 
@@ -128,11 +357,175 @@ impl Blobs {
         Ok(())
     }
 
+    /// Import all files below a directory, and create a collection of them.
+    ///
+    /// `path` should be a path valid for the file system on which the node runs.
+    /// The names in the resulting collection are the paths of the files relative to
+    /// `path`, using `/` as the separator. See [`AddDirectoryOptions`] for how files are
+    /// selected.
+    ///
+    /// Progress is reported per file to `cb`. If a file fails to import, an `Abort` event
+    /// naming the file is emitted and the import is stopped. If the directory can not be
+    /// scanned, an `Abort` event without a path is emitted.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn add_directory(
+        &self,
+        path: String,
+        options: AddDirectoryOptions,
+        cb: Option<Arc<dyn AddDirectoryCallback>>,
+    ) -> Result<AddDirectoryOutcome, IrohError> {
+        let filter = DirectoryFilter::new(&options)?;
+        let scan = tokio::task::spawn_blocking(move || {
+            let root = std::fs::canonicalize(path)?;
+            filter.scan(&root)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|res| res);
+        let files = match scan {
+            Ok(files) => files,
+            Err(err) => {
+                let err = err.context("failed to scan the directory");
+                if let Some(ref cb) = cb {
+                    let abort = AddDirectoryProgress::Abort(AddDirectoryProgressAbort {
+                        path: None,
+                        error: format!("{err:#}"),
+                    });
+                    cb.progress(Arc::new(abort)).await?;
+                }
+                return Err(err.into());
+            }
+        };
+
+        let import_mode = if options.in_place {
+            iroh::blobs::store::ImportMode::TryReference
+        } else {
+            iroh::blobs::store::ImportMode::Copy
+        };
+        let opts = iroh::client::blobs::AddFileOpts {
+            import_mode,
+            format: iroh::blobs::BlobFormat::Raw,
+        };
+
+        let batch = self.client().blobs().batch().await?;
+        let mut collection = iroh::blobs::format::collection::Collection::default();
+        let mut hashes = HashMap::new();
+        // keep the children alive until the collection is tagged
+        let mut temp_tags = Vec::with_capacity(files.len());
+        let mut size = 0;
+        for (name, file_path, file_size) in files {
+            if let Some(ref cb) = cb {
+                let found = AddDirectoryProgress::Found(AddDirectoryProgressFound {
+                    path: name.clone(),
+                    size: file_size,
+                });
+                cb.progress(Arc::new(found)).await?;
+            }
+            let (temp_tag, file_size) = match batch.add_file_with_opts(file_path, opts).await {
+                Ok(res) => res,
+                Err(err) => {
+                    let err = err.context(format!("failed to import {name}"));
+                    if let Some(ref cb) = cb {
+                        let abort = AddDirectoryProgress::Abort(AddDirectoryProgressAbort {
+                            path: Some(name),
+                            error: format!("{err:#}"),
+                        });
+                        cb.progress(Arc::new(abort)).await?;
+                    }
+                    return Err(err.into());
+                }
+            };
+            let hash = *temp_tag.hash();
+            size += file_size;
+            if let Some(ref cb) = cb {
+                let done = AddDirectoryProgress::Done(AddDirectoryProgressDone {
+                    path: name.clone(),
+                    hash: Arc::new(hash.into()),
+                });
+                cb.progress(Arc::new(done)).await?;
+            }
+            collection.push(name.clone(), hash);
+            hashes.insert(name, Arc::new(hash.into()));
+            temp_tags.push(temp_tag);
+        }
+
+        let temp_tag = batch.add_collection(collection.clone()).await?;
+        let hash = *temp_tag.hash();
+        let tag = batch
+            .persist_with_opts(temp_tag, (*options.tag).clone().into())
+            .await?;
+        drop(temp_tags);
+
+        Ok(AddDirectoryOutcome {
+            hash: Arc::new(hash.into()),
+            tag: tag.0.to_vec(),
+            collection: Arc::new(collection.into()),
+            files: hashes,
+            size,
+        })
+    }
+
+    /// Start adding a blob from data that is written incrementally.
+    ///
+    /// Data is passed in through [`BlobWriter::write`], and the import is completed by
+    /// calling [`BlobWriter::finish`]. Import progress is reported to `cb`. Calling
+    /// [`BlobWriter::abort`] or dropping the writer before it is finished discards the data.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn add_stream(
+        &self,
+        tag: Arc<SetTagOption>,
+        cb: Arc<dyn AddCallback>,
+    ) -> Result<Arc<BlobWriter>, IrohError> {
+        let (sender, receiver) = flume::bounded(BLOB_WRITER_CAPACITY);
+        let mut stream = self
+            .client()
+            .blobs()
+            .add_stream(receiver.into_stream(), (*tag).clone().into())
+            .await?;
+
+        let task = tokio::task::spawn(async move {
+            let mut size = 0;
+            while let Some(progress) = stream.next().await {
+                let progress = progress?;
+                let outcome = match &progress {
+                    iroh::blobs::provider::AddProgress::Found { size: s, .. } => {
+                        size += s;
+                        None
+                    }
+                    iroh::blobs::provider::AddProgress::AllDone { hash, format, tag } => {
+                        Some(Ok(BlobAddOutcome {
+                            hash: Arc::new((*hash).into()),
+                            format: (*format).into(),
+                            size,
+                            tag: tag.0.to_vec(),
+                        }))
+                    }
+                    iroh::blobs::provider::AddProgress::Abort(err) => {
+                        Some(Err(anyhow::anyhow!(err.to_string()).into()))
+                    }
+                    _ => None,
+                };
+                cb.progress(Arc::new(progress.into())).await?;
+                if let Some(outcome) = outcome {
+                    return outcome;
+                }
+            }
+            Err(anyhow::anyhow!("add stream ended before the blob was stored").into())
+        });
+
+        Ok(Arc::new(BlobWriter {
+            sender: Mutex::new(Some(sender)),
+            task: Mutex::new(Some(task)),
+            runtime: tokio::runtime::Handle::current(),
+        }))
+    }
+
     /// Export the blob contents to a file path
     /// The `path` field is expected to be the absolute path.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn write_to_path(&self, hash: Arc<Hash>, path: String) -> Result<(), IrohError> {
         let mut reader = self.client().blobs().read(hash.0).await?;
+        self.node.record_access(hash.0);
         let path: PathBuf = path.into();
         if let Some(dir) = path.parent() {
             tokio::fs::create_dir_all(dir)
@@ -171,6 +564,11 @@ impl Blobs {
     }
 
     /// Download a blob from another node and add it to the local database.
+    ///
+    /// If the options restrict the download to a set of ranges, only those ranges are
+    /// fetched and verified. The rest of the blob can be downloaded later, skipping the
+    /// data that is already present. Ranged downloads are only supported on nodes running in
+    /// this process, not on nodes connected to over RPC.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn download(
         &self,
@@ -178,11 +576,7 @@ impl Blobs {
         opts: Arc<BlobDownloadOptions>,
         cb: Arc<dyn DownloadCallback>,
     ) -> Result<(), IrohError> {
-        let mut stream = self
-            .client()
-            .blobs()
-            .download_with_opts(hash.0, opts.0.clone())
-            .await?;
+        let mut stream = self.download_stream(hash.0, &opts).await?;
         while let Some(progress) = stream.next().await {
             let progress = progress?;
             cb.progress(Arc::new(progress.into())).await?;
@@ -190,6 +584,196 @@ impl Blobs {
         Ok(())
     }
 
+    /// Download a collection from another node, including all nested collections.
+    ///
+    /// `opts` must use [`BlobFormat::HashSeq`]. Each nested collection is downloaded once its
+    /// parent is complete, from the same nodes. Progress events of all collections are passed
+    /// to `cb`, with ids that are unique across the whole tree, followed by a single `AllDone`
+    /// event.
+    ///
+    /// The tag of the root only protects its direct children from garbage collection, so
+    /// nested collections are tagged as well, with names derived from the root: with a named
+    /// tag `tag`, the nested collection at path `dir/sub` is tagged `tag/dir/sub`, with an
+    /// automatic tag it is tagged `<root hash>/dir/sub`. Downloading the same tree again
+    /// replaces these tags. A collection that is nested at several paths is downloaded and
+    /// tagged once, at the first path.
+    ///
+    /// The tree comes from another node, so the download fails if collections are nested more
+    /// than 32 levels deep, or if the collections hold more than 100 000 entries in total.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn download_recursive(
+        &self,
+        hash: Arc<Hash>,
+        opts: Arc<BlobDownloadOptions>,
+        cb: Arc<dyn DownloadCallback>,
+    ) -> Result<(), IrohError> {
+        if !matches!(opts.opts.format, iroh::blobs::BlobFormat::HashSeq) {
+            return Err(anyhow::anyhow!("recursive downloads require the HashSeq format").into());
+        }
+        if opts.ranges.is_some() {
+            return Err(anyhow::anyhow!("recursive downloads do not support ranges").into());
+        }
+
+        use iroh::blobs::{get::db::DownloadProgress, util::SetTagOption};
+
+        let root = hash.0;
+        let mut total = iroh::blobs::get::Stats::default();
+        let mut ids = IdRemap::default();
+        let mut visited = HashSet::new();
+        let mut entries = 0;
+        let mut stack = vec![(String::new(), root, 0)];
+        while let Some((path, hash, depth)) = stack.pop() {
+            if !visited.insert(hash) {
+                continue;
+            }
+            let tag = match (&opts.opts.tag, path.is_empty()) {
+                (tag, true) => tag.clone(),
+                (SetTagOption::Named(tag), false) => {
+                    let mut name = tag.0.to_vec();
+                    name.push(b'/');
+                    name.extend_from_slice(path.as_bytes());
+                    SetTagOption::Named(bytes::Bytes::from(name).into())
+                }
+                (SetTagOption::Auto, false) => {
+                    SetTagOption::Named(iroh::blobs::Tag::from(format!("{root}/{path}")))
+                }
+            };
+            let level = BlobDownloadOptions {
+                opts: iroh::client::blobs::DownloadOptions {
+                    tag,
+                    ..opts.opts.clone()
+                },
+                ranges: None,
+                skip_complete: opts.skip_complete,
+            };
+            let mut stream = self.download_stream(hash, &level).await?;
+            ids.reset();
+            while let Some(progress) = stream.next().await {
+                let progress = match progress? {
+                    DownloadProgress::AllDone(stats) => {
+                        add_stats(&mut total, stats);
+                        continue;
+                    }
+                    DownloadProgress::Found {
+                        id,
+                        child,
+                        hash,
+                        size,
+                    } => DownloadProgress::Found {
+                        id: ids.found(id),
+                        child,
+                        hash,
+                        size,
+                    },
+                    DownloadProgress::Progress { id, offset } => DownloadProgress::Progress {
+                        id: ids.get(id),
+                        offset,
+                    },
+                    DownloadProgress::Done { id } => DownloadProgress::Done { id: ids.get(id) },
+                    progress => progress,
+                };
+                cb.progress(Arc::new(progress.into())).await?;
+            }
+
+            let collection = self.client().blobs().get_collection(hash).await?;
+            entries += collection.len();
+            check_tree_limits(depth, entries)?;
+            let nested = collection.iter().filter_map(|(name, hash)| {
+                let dir = nested_collection_name(name)?;
+                let path = if path.is_empty() {
+                    dir.to_string()
+                } else {
+                    format!("{path}/{dir}")
+                };
+                Some((path, *hash, depth + 1))
+            });
+            let nested: Vec<_> = nested.collect();
+            stack.extend(nested.into_iter().rev());
+        }
+
+        cb.progress(Arc::new(DownloadProgress::AllDone(total).into()))
+            .await?;
+        Ok(())
+    }
+
+    /// Start downloading a blob from another node, without waiting for it to finish.
+    ///
+    /// The returned [`DownloadHandle`] can be used to follow the progress of the download,
+    /// wait for it to finish, or cancel it. Progress events are also passed to `cb`, if set.
+    ///
+    /// For downloads with [`DownloadMode::Queued`] and an automatic tag, the name of the tag is
+    /// picked when the download starts, and shared with [`DownloadHandle::add_providers`].
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn download_start(
+        &self,
+        hash: Arc<Hash>,
+        opts: Arc<BlobDownloadOptions>,
+        cb: Option<Arc<dyn DownloadCallback>>,
+    ) -> Result<Arc<DownloadHandle>, IrohError> {
+        let mut opts = BlobDownloadOptions {
+            opts: opts.opts.clone(),
+            ranges: opts.ranges.clone(),
+            skip_complete: opts.skip_complete,
+        };
+        if matches!(opts.opts.mode, iroh::client::blobs::DownloadMode::Queued)
+            && matches!(opts.opts.tag, iroh::blobs::util::SetTagOption::Auto)
+        {
+            let tag = self.queued_download_tag().await?;
+            opts.opts.tag = iroh::blobs::util::SetTagOption::Named(tag);
+        }
+        let mut stream = self.download_stream(hash.0, &opts).await?;
+        let cancel = CancellationToken::new();
+        let state = Arc::new(std::sync::Mutex::new(DownloadState::default()));
+
+        let task = tokio::task::spawn({
+            let cancel = cancel.clone();
+            let state = state.clone();
+            async move {
+                loop {
+                    tokio::select! {
+                        biased;
+
+                        _ = cancel.cancelled() => {
+                            return Err(DownloadError::Cancelled);
+                        }
+                        progress = stream.next() => {
+                            let Some(progress) = progress else {
+                                return Err(anyhow::anyhow!("download ended unexpectedly").into());
+                            };
+                            let progress: DownloadProgress = progress?.into();
+                            state.lock().unwrap().update(&progress);
+                            if let Some(ref cb) = cb {
+                                cb.progress(Arc::new(progress.clone())).await.map_err(anyhow::Error::from)?;
+                            }
+                            match progress {
+                                DownloadProgress::AllDone(done) => return Ok(done),
+                                DownloadProgress::Abort(abort) => {
+                                    return Err(DownloadError::Failed { message: abort.error });
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+            }
+        });
+        let done = async move {
+            match task.await {
+                Ok(res) => res,
+                Err(err) => Err(anyhow::Error::from(err).into()),
+            }
+        };
+
+        Ok(Arc::new(DownloadHandle {
+            node: self.node.clone(),
+            hash: hash.0,
+            opts: opts.opts,
+            cancel,
+            state,
+            done: done.boxed().shared(),
+        }))
+    }
+
     /// Export a blob from the internal blob store to a path on the node's filesystem.
     ///
     /// `destination` should be a writeable, absolute path on the local node's filesystem.
@@ -221,10 +805,150 @@ impl Blobs {
             .await?;
 
         stream.finish().await?;
+        self.node.record_access(hash.0);
+
+        Ok(())
+    }
+
+    /// Export a blob from the internal blob store to a path on the node's filesystem,
+    /// reporting progress.
+    ///
+    /// Behaves like [`Blobs::export`], but calls `cb` with an event for every file that is
+    /// found, written to and finished. For [`BlobExportFormat::Collection`] there is one set of
+    /// events per child of the collection.
+    ///
+    /// Returning an error from the callback aborts the export. Files that were already
+    /// written are left in place.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn export_with_progress(
+        &self,
+        hash: Arc<Hash>,
+        destination: String,
+        format: BlobExportFormat,
+        mode: BlobExportMode,
+        cb: Arc<dyn BlobExportCallback>,
+    ) -> Result<(), IrohError> {
+        let destination: PathBuf = destination.into();
+        if let Some(dir) = destination.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(anyhow::Error::from)?;
+        }
+
+        let mut stream = self
+            .client()
+            .blobs()
+            .export(hash.0, destination, format.into(), mode.into())
+            .await?;
+
+        while let Some(progress) = stream.next().await {
+            let progress = progress?;
+            if let iroh::blobs::export::ExportProgress::Abort(ref err) = progress {
+                let err = anyhow::anyhow!("export aborted: {}", err);
+                cb.progress(Arc::new(progress.into())).await?;
+                return Err(err.into());
+            }
+            cb.progress(Arc::new(progress.into())).await?;
+        }
+        self.node.record_access(hash.0);
+
+        Ok(())
+    }
 
+    /// Export a collection to a directory tree on the node's filesystem.
+    ///
+    /// Every entry of the collection, including the entries of nested collections, is
+    /// exported to a file at its path relative to `destination`, creating directories as
+    /// needed. See [`Blobs::get_collection_recursive`] for how paths are derived.
+    ///
+    /// If set, `cb` is called with the progress of every exported file. Returning an error
+    /// from the callback aborts the export.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn export_tree(
+        &self,
+        hash: Arc<Hash>,
+        destination: String,
+        mode: BlobExportMode,
+        cb: Option<Arc<dyn BlobExportCallback>>,
+    ) -> Result<(), IrohError> {
+        let destination = PathBuf::from(destination);
+        let tree = self.collection_tree(hash.0).await?;
+        let mode: iroh::blobs::store::ExportMode = mode.into();
+        for (id, (name, hash)) in tree.iter().enumerate() {
+            let path = name
+                .split('/')
+                .try_fold(destination.clone(), |path, component| match component {
+                    "" | "." | ".." => None,
+                    component => Some(path.join(component)),
+                })
+                .ok_or_else(|| anyhow::anyhow!("invalid path in collection: {name:?}"))?;
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir)
+                    .await
+                    .map_err(anyhow::Error::from)?;
+            }
+            let mut stream = self
+                .client()
+                .blobs()
+                .export(*hash, path, iroh::blobs::store::ExportFormat::Blob, mode)
+                .await?;
+            while let Some(progress) = stream.next().await {
+                let mut progress: DocExportProgress = progress?.into();
+                // report one id per file, across all exports
+                match progress {
+                    DocExportProgress::Found(ref mut p) => p.id = id as u64,
+                    DocExportProgress::Progress(ref mut p) => p.id = id as u64,
+                    DocExportProgress::Done(ref mut p) => p.id = id as u64,
+                    DocExportProgress::AllDone => continue,
+                    DocExportProgress::Abort(ref a) => {
+                        let err = anyhow::anyhow!("export of {name:?} aborted: {}", a.error);
+                        if let Some(ref cb) = cb {
+                            cb.progress(Arc::new(progress.clone())).await?;
+                        }
+                        return Err(err.into());
+                    }
+                }
+                if let Some(ref cb) = cb {
+                    cb.progress(Arc::new(progress)).await?;
+                }
+            }
+        }
+        if let Some(ref cb) = cb {
+            cb.progress(Arc::new(DocExportProgress::AllDone)).await?;
+        }
         Ok(())
     }
 
+    /// Protect a blob from garbage collection for `duration`.
+    ///
+    /// The returned [`BlobLease`] keeps the data alive without creating a permanent tag.
+    /// With [`BlobFormat::HashSeq`] the children of the hash sequence are protected as well.
+    /// The lease ends when it expires, when it is released or dropped, or when the node shuts
+    /// down.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn protect(
+        &self,
+        hash: Arc<Hash>,
+        format: BlobFormat,
+        duration: Duration,
+    ) -> Result<Arc<BlobLease>, IrohError> {
+        let content = iroh::blobs::HashAndFormat {
+            hash: hash.0,
+            format: format.into(),
+        };
+        let batch = self.client().blobs().batch().await?;
+        let temp_tag = batch.temp_tag(content).await?;
+        let lease = Arc::new(LeaseInner {
+            state: std::sync::Mutex::new(LeaseState {
+                deadline: tokio::time::Instant::now() + duration,
+                held: Some((batch, temp_tag)),
+            }),
+            changed: tokio::sync::Notify::new(),
+        });
+        tokio::task::spawn(expire_lease(lease.clone()));
+        Ok(Arc::new(BlobLease(lease)))
+    }
+
     /// Create a ticket for sharing a blob from this node.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn share(
@@ -244,7 +968,7 @@ impl Blobs {
     /// List all incomplete (partial) blobs.
     ///
     /// Note: this allocates for each `BlobListIncompleteResponse`, if you have many `BlobListIncompleteResponse`s this may be a prohibitively large list.
-    /// Please file an [issue](https://github.com/n0-computer/iroh-ffi/issues/new) if you run into this issue
+    /// Use [`Self::list_incomplete_iter`] to enumerate the blobs in batches instead.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn list_incomplete(&self) -> Result<Vec<IncompleteBlobInfo>, IrohError> {
         let blobs = self
@@ -258,10 +982,26 @@ impl Blobs {
         Ok(blobs)
     }
 
+    /// Iterate over all incomplete (partial) blobs.
+    ///
+    /// Blobs are fetched lazily, in batches, via [`IncompleteBlobListIterator::next_batch`].
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn list_incomplete_iter(&self) -> Result<Arc<IncompleteBlobListIterator>, IrohError> {
+        let stream = self
+            .client()
+            .blobs()
+            .list_incomplete()
+            .await?
+            .map_ok(IncompleteBlobInfo::from);
+        Ok(Arc::new(IncompleteBlobListIterator(BatchStream::new(
+            stream,
+        ))))
+    }
+
     /// List all collections.
     ///
     /// Note: this allocates for each `BlobListCollectionsResponse`, if you have many `BlobListCollectionsResponse`s this may be a prohibitively large list.
-    /// Please file an [issue](https://github.com/n0-computer/iroh-ffi/issues/new) if you run into this issue
+    /// Use [`Self::list_collections_iter`] to enumerate the collections in batches instead.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn list_collections(&self) -> Result<Vec<CollectionInfo>, IrohError> {
         let blobs = self
@@ -274,6 +1014,19 @@ impl Blobs {
         Ok(blobs)
     }
 
+    /// Iterate over all collections.
+    ///
+    /// Collections are fetched lazily, in batches, via [`CollectionListIterator::next_batch`].
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn list_collections_iter(&self) -> Result<Arc<CollectionListIterator>, IrohError> {
+        let stream = self
+            .client()
+            .blobs()
+            .list_collections()?
+            .map_ok(CollectionInfo::from);
+        Ok(Arc::new(CollectionListIterator(BatchStream::new(stream))))
+    }
+
     /// Read the content of a collection
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn get_collection(&self, hash: Arc<Hash>) -> Result<Arc<Collection>, IrohError> {
@@ -282,16 +1035,49 @@ impl Blobs {
         Ok(Arc::new(collection.into()))
     }
 
-    /// Create a collection from already existing blobs.
+    /// Read the content of a collection, including the content of all nested collections.
     ///
-    /// To automatically clear the tags for the passed in blobs you can set
-    /// `tags_to_delete` on those tags, and they will be deleted once the collection is created.
+    /// Entries of nested collections (see [`Collection::push_collection`]) are replaced by
+    /// the entries of the collection they point to, named by their path relative to the root
+    /// collection, e.g. `dir/sub/file.txt`. All nested collections must be available locally.
+    ///
+    /// Fails if collections are nested more than 32 levels deep, or if the collections hold
+    /// more than 100 000 entries in total, counting a collection again at every path it is
+    /// nested at.
     #[uniffi::method(async_runtime = "tokio")]
-    pub async fn create_collection(
+    pub async fn get_collection_recursive(
         &self,
-        collection: Arc<Collection>,
-        tag: Arc<SetTagOption>,
-        tags_to_delete: Vec<String>,
+        hash: Arc<Hash>,
+    ) -> Result<Arc<Collection>, IrohError> {
+        let tree = self.collection_tree(hash.0).await?;
+        Ok(Arc::new(tree.into()))
+    }
+
+    /// Compare two collections by entry name.
+    ///
+    /// Both collections must be available locally. Entries are reported in the order they
+    /// appear in `new`, removed entries in the order they appear in `old`.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn diff_collections(
+        &self,
+        old_hash: Arc<Hash>,
+        new_hash: Arc<Hash>,
+    ) -> Result<CollectionDiff, IrohError> {
+        let old = self.client().blobs().get_collection(old_hash.0).await?;
+        let new = self.client().blobs().get_collection(new_hash.0).await?;
+        Ok(CollectionDiff::new(&old, &new))
+    }
+
+    /// Create a collection from already existing blobs.
+    ///
+    /// To automatically clear the tags for the passed in blobs you can set
+    /// `tags_to_delete` on those tags, and they will be deleted once the collection is created.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn create_collection(
+        &self,
+        collection: Arc<Collection>,
+        tag: Arc<SetTagOption>,
+        tags_to_delete: Vec<String>,
     ) -> Result<HashAndTag, IrohError> {
         let collection = collection.0.read().unwrap().clone();
         let (hash, tag) = self
@@ -313,26 +1099,424 @@ impl Blobs {
         })
     }
 
-    /// Delete a blob.
+    /// Get the names of all tags that reference the blob directly.
+    ///
+    /// The node keeps an index of its tags by hash, so this does not walk all tags. Clients
+    /// created with [`Iroh::client`] cannot access that index, and list all tags of the node
+    /// instead.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn tags_for(&self, hash: Arc<Hash>) -> Result<Vec<Vec<u8>>, IrohError> {
+        let tags = self.tags_referencing(hash.0).await?;
+        Ok(tags.into_iter().map(|tag| tag.0.to_vec()).collect())
+    }
+
+    /// Check the integrity of the blob store.
+    ///
+    /// Verifies the data of every complete and partial blob against its BLAKE3 outboard and
+    /// checks that all tagged blobs, and the children of tagged hash sequences, are present.
+    /// The status of each blob is reported to `cb` as a `BlobValidateProgress` event.
+    ///
+    /// With `repair` set, inconsistencies of the store itself are fixed where possible and
+    /// corrupt blobs are dropped from the store, so they can be downloaded again.
+    /// Not available for RPC clients.
     #[uniffi::method(async_runtime = "tokio")]
-    pub async fn delete_blob(&self, hash: Arc<Hash>) -> Result<(), IrohError> {
-        let mut tags = self.client().tags().list().await?;
+    pub async fn validate(
+        &self,
+        repair: bool,
+        cb: Arc<dyn BlobValidateCallback>,
+    ) -> Result<(), IrohError> {
+        // checked before the consistency check, which would already repair the remote store
+        if let Iroh::Client(_) = self.node {
+            return Err(anyhow::anyhow!("validate is not available for RPC clients").into());
+        }
+        let mut check = self.client().blobs().consistency_check(repair).await?;
+        while let Some(progress) = check.next().await {
+            use iroh::blobs::store::{ConsistencyCheckProgress, ReportLevel};
+            match progress? {
+                ConsistencyCheckProgress::Update {
+                    message,
+                    entry,
+                    level: ReportLevel::Warn | ReportLevel::Error,
+                } => {
+                    let warning = BlobValidateProgress::Warning(BlobValidateWarning {
+                        hash: entry.map(|hash| Arc::new(hash.into())),
+                        message,
+                    });
+                    cb.progress(Arc::new(warning)).await?;
+                }
+                ConsistencyCheckProgress::Abort(err) => return Err(anyhow::anyhow!(err).into()),
+                _ => {}
+            }
+        }
 
-        let mut name = None;
-        while let Some(tag) = tags.next().await {
-            let tag = tag?;
-            if tag.hash == hash.0 {
-                name = Some(tag.name);
+        let (sender, receiver) = flume::bounded(32);
+        let run = match &self.node {
+            Iroh::Fs(node, store, _) => {
+                let store = store.clone();
+                node.local_pool_handle()
+                    .try_spawn(move || validate_store(store, repair, sender))
             }
+            Iroh::Memory(node, store, _) => {
+                let store = store.clone();
+                node.local_pool_handle()
+                    .try_spawn(move || validate_store(store, repair, sender))
+            }
+            Iroh::Client(_) => unreachable!("rejected above"),
+        }
+        .map_err(anyhow::Error::from)?;
+
+        while let Ok(progress) = receiver.recv_async().await {
+            cb.progress(Arc::new(progress)).await?;
+        }
+        run.await.map_err(anyhow::Error::from)??;
+        Ok(())
+    }
+
+    /// Get statistics about the storage used by the blob store.
+    ///
+    /// For in-memory nodes all complete data and outboards are counted as inline.
+    /// Not available for RPC clients.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn store_stats(&self) -> Result<StoreStats, IrohError> {
+        let stats = match &self.node {
+            Iroh::Fs(node, store, state) => {
+                let blobs_dir = state.blobs_dir.as_deref();
+                store_stats(store, blobs_dir, node.local_pool_handle()).await?
+            }
+            Iroh::Memory(node, store, _) => {
+                store_stats(store, None, node.local_pool_handle()).await?
+            }
+            Iroh::Client(_) => {
+                return Err(anyhow::anyhow!("store stats are not available for RPC clients").into())
+            }
+        };
+        Ok(stats)
+    }
+
+    /// Delete a blob.
+    ///
+    /// If the blob is referenced by any tags, this fails with [`DeleteBlobError::Tagged`],
+    /// listing those tags, unless `force` is set. With `force`, all tags referencing the blob
+    /// are removed before it is deleted.
+    ///
+    /// Returns the names of the removed tags.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn delete_blob(
+        &self,
+        hash: Arc<Hash>,
+        force: bool,
+    ) -> Result<Vec<Vec<u8>>, DeleteBlobError> {
+        let tags = self.tags_referencing(hash.0).await?;
+        if !tags.is_empty() && !force {
+            return Err(DeleteBlobError::Tagged {
+                tags: tags.into_iter().map(|tag| tag.0.to_vec()).collect(),
+            });
+        }
+
+        let mut removed = Vec::with_capacity(tags.len());
+        for tag in tags {
+            self.client().tags().delete(tag.clone()).await?;
+            removed.push(tag.0.to_vec());
+        }
+        self.client().blobs().delete_blob(hash.0).await?;
+
+        Ok(removed)
+    }
+}
+
+/// Error returned by [`Blobs::delete_blob`].
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum DeleteBlobError {
+    /// The blob is still referenced by tags, and deletion was not forced.
+    #[error("blob is referenced by {} tag(s)", tags.len())]
+    Tagged { tags: Vec<Vec<u8>> },
+    /// The deletion failed.
+    #[error("{message}")]
+    Failed { message: String },
+}
+
+impl From<anyhow::Error> for DeleteBlobError {
+    fn from(e: anyhow::Error) -> Self {
+        DeleteBlobError::Failed {
+            message: format!("{e:?}"),
+        }
+    }
+}
+
+/// A streaming reader for a single blob.
+///
+/// Created via [`Blobs::open_reader`].
+#[derive(uniffi::Object)]
+pub struct BlobReader {
+    node: Iroh,
+    hash: iroh::blobs::Hash,
+    size: u64,
+    is_complete: bool,
+    state: Mutex<BlobReaderState>,
+}
+
+struct BlobReaderState {
+    reader: iroh::client::blobs::Reader,
+    position: u64,
+}
+
+impl BlobReader {
+    fn check_offset(&self, offset: u64) -> Result<(), IrohError> {
+        if offset > self.size {
+            return Err(anyhow::anyhow!(
+                "offset {} is past the end of the blob ({} bytes)",
+                offset,
+                self.size
+            )
+            .into());
         }
+        Ok(())
+    }
+}
+
+#[uniffi::export]
+impl BlobReader {
+    /// The total size of the blob.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Whether the blob is fully available locally.
+    ///
+    /// Returns false for partial blobs for which some chunks are missing.
+    pub fn is_complete(&self) -> bool {
+        self.is_complete
+    }
+
+    /// The current position of the reader within the blob.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn position(&self) -> u64 {
+        self.state.lock().await.position
+    }
+
+    /// Read up to `max_len` bytes from the current position.
+    ///
+    /// Returns fewer bytes only when the end of the blob is reached, and an empty
+    /// buffer once the reader is exhausted.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn read(&self, max_len: u64) -> Result<Vec<u8>, IrohError> {
+        let mut state = self.state.lock().await;
+        let mut buf = Vec::new();
+        AsyncReadExt::take(&mut state.reader, max_len)
+            .read_to_end(&mut buf)
+            .await
+            .map_err(anyhow::Error::from)?;
+        state.position += buf.len() as u64;
+        Ok(buf)
+    }
+
+    /// Move the reader to `offset`, counted from the start of the blob.
+    ///
+    /// Subsequent calls to [`Self::read`] continue from this position.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn seek(&self, offset: u64) -> Result<(), IrohError> {
+        self.check_offset(offset)?;
+        let mut state = self.state.lock().await;
+        let reader = self
+            .node
+            .inner_client()
+            .blobs()
+            .read_at(self.hash, offset, iroh::client::blobs::ReadAtLen::All)
+            .await?;
+        state.reader = reader;
+        state.position = offset;
+        Ok(())
+    }
+
+    /// Read up to `len` bytes starting at `offset`, without moving the reader.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn read_at(&self, offset: u64, len: u64) -> Result<Vec<u8>, IrohError> {
+        self.check_offset(offset)?;
+        let res = self
+            .node
+            .inner_client()
+            .blobs()
+            .read_at_to_bytes(
+                self.hash,
+                offset,
+                iroh::client::blobs::ReadAtLen::AtMost(len),
+            )
+            .await?;
+        Ok(res.to_vec())
+    }
+}
+
+/// Number of chunks buffered by a [`BlobWriter`] before [`BlobWriter::write`] waits
+/// for the store to catch up.
+const BLOB_WRITER_CAPACITY: usize = 16;
+
+/// A writer that adds a blob from data passed in chunk by chunk.
+///
+/// Created via [`Blobs::add_stream`].
+#[derive(uniffi::Object)]
+pub struct BlobWriter {
+    sender: Mutex<Option<flume::Sender<std::io::Result<bytes::Bytes>>>>,
+    task: Mutex<Option<tokio::task::JoinHandle<Result<BlobAddOutcome, IrohError>>>>,
+    /// Used to abort the import when the writer is dropped, possibly outside of the runtime.
+    runtime: tokio::runtime::Handle,
+}
 
-        if let Some(name) = name {
-            self.client().tags().delete(name).await?;
-            self.client().blobs().delete_blob((*hash).clone().0).await?;
+/// The error passed to the import to make it fail instead of storing the data written so far.
+fn blob_writer_aborted() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Interrupted, "blob writer aborted")
+}
+
+impl Drop for BlobWriter {
+    fn drop(&mut self) {
+        if let Some(sender) = self.sender.get_mut().take() {
+            self.runtime.spawn(async move {
+                sender.send_async(Err(blob_writer_aborted())).await.ok();
+            });
         }
+    }
+}
+
+impl BlobWriter {
+    async fn join(&self) -> Result<BlobAddOutcome, IrohError> {
+        let task = self
+            .task
+            .lock()
+            .await
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("blob writer is already finished"))?;
+        task.await.map_err(anyhow::Error::from)?
+    }
+}
 
+#[uniffi::export]
+impl BlobWriter {
+    /// Write the next chunk of data.
+    ///
+    /// Waits if the store has not yet caught up with previously written chunks.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn write(&self, chunk: Vec<u8>) -> Result<(), IrohError> {
+        let sender = self.sender.lock().await;
+        let sender = sender
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("blob writer is already finished"))?;
+        if sender.send_async(Ok(chunk.into())).await.is_err() {
+            // The import stopped early, surface the reason it did so.
+            self.join().await?;
+            return Err(anyhow::anyhow!("blob import stopped unexpectedly").into());
+        }
         Ok(())
     }
+
+    /// Signal the end of the data, and wait for the blob to be stored.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn finish(&self) -> Result<BlobAddOutcome, IrohError> {
+        self.sender.lock().await.take();
+        self.join().await
+    }
+
+    /// Discard the data written so far, and wait for the import to stop.
+    ///
+    /// Nothing is stored and no tag is created.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn abort(&self) -> Result<(), IrohError> {
+        let sender = self
+            .sender
+            .lock()
+            .await
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("blob writer is already finished"))?;
+        // The import may already have stopped, in which case there is nothing to abort.
+        sender.send_async(Err(blob_writer_aborted())).await.ok();
+        drop(sender);
+        match self.join().await {
+            Ok(_) => Err(anyhow::anyhow!("blob was stored before the import was aborted").into()),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// A stream of list results, consumed in batches.
+pub(crate) struct BatchStream<T>(Mutex<BoxStream<'static, anyhow::Result<T>>>);
+
+impl<T> BatchStream<T> {
+    pub(crate) fn new(stream: impl Stream<Item = anyhow::Result<T>> + Send + 'static) -> Self {
+        BatchStream(Mutex::new(stream.boxed()))
+    }
+
+    /// Pull up to `n` items from the stream.
+    ///
+    /// Returns an empty list once the stream is exhausted.
+    pub(crate) async fn next_batch(&self, n: u32) -> Result<Vec<T>, IrohError> {
+        let mut stream = self.0.lock().await;
+        let mut items = Vec::new();
+        while items.len() < n as usize {
+            match stream.next().await {
+                Some(item) => items.push(item?),
+                None => break,
+            }
+        }
+        Ok(items)
+    }
+}
+
+/// Iterator over complete blobs, created via [`Blobs::list_iter`].
+#[derive(uniffi::Object)]
+pub struct BlobListIterator(BatchStream<Arc<Hash>>);
+
+#[uniffi::export]
+impl BlobListIterator {
+    /// Get the next `n` blob hashes.
+    ///
+    /// Returns an empty list once all blobs have been returned.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn next_batch(&self, n: u32) -> Result<Vec<Arc<Hash>>, IrohError> {
+        self.0.next_batch(n).await
+    }
+}
+
+/// Iterator over complete blobs and their details, created via [`Blobs::list_info_iter`].
+#[derive(uniffi::Object)]
+pub struct BlobInfoListIterator(BatchStream<BlobInfo>);
+
+#[uniffi::export]
+impl BlobInfoListIterator {
+    /// Get the next `n` blobs.
+    ///
+    /// Returns an empty list once all blobs have been returned.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn next_batch(&self, n: u32) -> Result<Vec<BlobInfo>, IrohError> {
+        self.0.next_batch(n).await
+    }
+}
+
+/// Iterator over incomplete blobs, created via [`Blobs::list_incomplete_iter`].
+#[derive(uniffi::Object)]
+pub struct IncompleteBlobListIterator(BatchStream<IncompleteBlobInfo>);
+
+#[uniffi::export]
+impl IncompleteBlobListIterator {
+    /// Get the next `n` incomplete blobs.
+    ///
+    /// Returns an empty list once all blobs have been returned.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn next_batch(&self, n: u32) -> Result<Vec<IncompleteBlobInfo>, IrohError> {
+        self.0.next_batch(n).await
+    }
+}
+
+/// Iterator over collections, created via [`Blobs::list_collections_iter`].
+#[derive(uniffi::Object)]
+pub struct CollectionListIterator(BatchStream<CollectionInfo>);
+
+#[uniffi::export]
+impl CollectionListIterator {
+    /// Get the next `n` collections.
+    ///
+    /// Returns an empty list once all collections have been returned.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn next_batch(&self, n: u32) -> Result<Vec<CollectionInfo>, IrohError> {
+        self.0.next_batch(n).await
+    }
 }
 
 /// The Hash and associated tag of a newly created collection
@@ -542,17 +1726,6 @@ impl From<Hash> for iroh::blobs::Hash {
     }
 }
 
-// /// Hash type used throughout Iroh. A blake3 hash.
-// #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Object)]
-// #[uniffi::export(Display)]
-// pub struct Tag(pub(crate) iroh::blobs::Tag);
-
-// impl From<iroh::blobs::Tag> for Tag {
-//     fn from(h: iroh::blobs::Tag) -> Self {
-//         Tag(h)
-//     }
-// }
-
 /// The `progress` method will be called for each `BlobProvideEvent` event that is
 /// emitted from the iroh node while the callback is registered. Use the `BlobProvideEvent.type()`
 /// method to check the `BlobProvideEventType`
@@ -890,6 +2063,15 @@ pub trait AddCallback: Send + Sync + 'static {
     async fn progress(&self, progress: Arc<AddProgress>) -> Result<(), CallbackError>;
 }
 
+/// The `progress` method will be called for each `DocExportProgress` event that is
+/// emitted during a `node.blobs_export_with_progress`. Use the `DocExportProgress.type()`
+/// method to check the `DocExportProgressType`
+#[uniffi::export(with_foreign)]
+#[async_trait::async_trait]
+pub trait BlobExportCallback: Send + Sync + 'static {
+    async fn progress(&self, progress: Arc<DocExportProgress>) -> Result<(), CallbackError>;
+}
+
 /// The different types of AddProgress events
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, uniffi::Enum)]
 pub enum AddProgressType {
@@ -1053,92 +2235,669 @@ impl AddProgress {
     }
 }
 
-/// A format identifier
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
-pub enum BlobFormat {
-    /// Raw blob
-    Raw,
-    /// A sequence of BLAKE3 hashes
-    HashSeq,
+/// How symbolic links are handled by [`Blobs::add_directory`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum SymlinkPolicy {
+    /// Ignore symbolic links.
+    Skip,
+    /// Follow symbolic links, importing the files and directories they point to.
+    Follow,
+    /// Fail the import if a symbolic link is found.
+    Error,
 }
 
-impl From<iroh::blobs::BlobFormat> for BlobFormat {
-    fn from(value: iroh::blobs::BlobFormat) -> Self {
-        match value {
-            iroh::blobs::BlobFormat::Raw => BlobFormat::Raw,
-            iroh::blobs::BlobFormat::HashSeq => BlobFormat::HashSeq,
-        }
-    }
+/// Options for [`Blobs::add_directory`].
+///
+/// Patterns are glob patterns matched against the path of a file relative to the
+/// imported directory, using `/` as the separator. `*` does not match `/`, use `**` to
+/// match across directories, e.g. `**/*.txt`.
+#[derive(Debug, uniffi::Record)]
+pub struct AddDirectoryOptions {
+    /// Only import files matching one of these patterns. If empty, all files are imported.
+    #[uniffi(default = [])]
+    pub include: Vec<String>,
+    /// Skip files and directories matching one of these patterns. Takes precedence
+    /// over `include`.
+    #[uniffi(default = [])]
+    pub exclude: Vec<String>,
+    /// How to handle symbolic links.
+    pub symlinks: SymlinkPolicy,
+    /// Import files and directories whose name starts with a `.`. Defaults to `false`.
+    #[uniffi(default = false)]
+    pub include_hidden: bool,
+    /// If true, Iroh will assume that the files will not change and will share them in
+    /// place without copying to the Iroh data directory. Defaults to `false`.
+    #[uniffi(default = false)]
+    pub in_place: bool,
+    /// The tag to set on the resulting collection.
+    pub tag: Arc<SetTagOption>,
 }
 
-impl From<BlobFormat> for iroh::blobs::BlobFormat {
-    fn from(value: BlobFormat) -> Self {
-        match value {
-            BlobFormat::Raw => iroh::blobs::BlobFormat::Raw,
-            BlobFormat::HashSeq => iroh::blobs::BlobFormat::HashSeq,
-        }
-    }
+/// The outcome of [`Blobs::add_directory`].
+#[derive(Debug, uniffi::Record)]
+pub struct AddDirectoryOutcome {
+    /// The hash of the collection.
+    pub hash: Arc<Hash>,
+    /// The tag of the collection.
+    pub tag: Vec<u8>,
+    /// The collection, named by relative path.
+    pub collection: Arc<Collection>,
+    /// The hash of each imported file, by relative path.
+    pub files: HashMap<String, Arc<Hash>>,
+    /// The total size of the imported files, in bytes.
+    pub size: u64,
 }
 
-/// Options to download  data specified by the hash.
-#[derive(Debug, uniffi::Object)]
-pub struct BlobDownloadOptions(iroh::client::blobs::DownloadOptions);
+/// Selects the files imported by [`Blobs::add_directory`] and [`crate::Doc::import_directory`].
+pub(crate) struct DirectoryFilter {
+    include: Vec<glob::Pattern>,
+    exclude: Vec<glob::Pattern>,
+    symlinks: SymlinkPolicy,
+    include_hidden: bool,
+}
 
-#[uniffi::export]
-impl BlobDownloadOptions {
-    /// Create a BlobDownloadRequest
-    #[uniffi::constructor]
-    pub fn new(
-        format: BlobFormat,
-        nodes: Vec<Arc<NodeAddr>>,
-        tag: Arc<SetTagOption>,
-    ) -> Result<Self, IrohError> {
-        Ok(BlobDownloadOptions(iroh::client::blobs::DownloadOptions {
-            format: format.into(),
-            nodes: nodes
-                .into_iter()
-                .map(|node| (*node).clone().try_into())
-                .collect::<Result<_, _>>()?,
-            tag: (*tag).clone().into(),
-            mode: iroh::client::blobs::DownloadMode::Direct,
-        }))
+impl DirectoryFilter {
+    const MATCH_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+
+    fn new(options: &AddDirectoryOptions) -> anyhow::Result<Self> {
+        let patterns = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| {
+                    glob::Pattern::new(p).map_err(|e| anyhow::anyhow!("invalid pattern {p:?}: {e}"))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+        Ok(DirectoryFilter {
+            include: patterns(&options.include)?,
+            exclude: patterns(&options.exclude)?,
+            symlinks: options.symlinks,
+            include_hidden: options.include_hidden,
+        })
     }
-}
 
-impl From<iroh::client::blobs::DownloadOptions> for BlobDownloadOptions {
-    fn from(value: iroh::client::blobs::DownloadOptions) -> Self {
-        BlobDownloadOptions(value)
+    /// Select all files, including hidden ones, skipping symbolic links.
+    pub(crate) fn all_files() -> Self {
+        DirectoryFilter {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            symlinks: SymlinkPolicy::Skip,
+            include_hidden: true,
+        }
     }
-}
 
-/// The expected format of a hash being exported.
-#[derive(Debug, uniffi::Enum)]
-pub enum BlobExportFormat {
-    /// The hash refers to any blob and will be exported to a single file.
-    Blob,
-    /// The hash refers to a [`crate::format::collection::Collection`] blob
-    /// and all children of the collection shall be exported to one file per child.
-    ///
-    /// If the blob can be parsed as a [`BlobFormat::HashSeq`], and the first child contains
-    /// collection metadata, all other children of the collection will be exported to
-    /// a file each, with their collection name treated as a relative path to the export
-    /// destination path.
-    ///
-    /// If the blob cannot be parsed as a collection, the operation will fail.
-    Collection,
-}
+    fn matches(patterns: &[glob::Pattern], name: &str) -> bool {
+        patterns
+            .iter()
+            .any(|p| p.matches_with(name, Self::MATCH_OPTIONS))
+    }
 
-impl From<BlobExportFormat> for iroh::blobs::store::ExportFormat {
-    fn from(value: BlobExportFormat) -> Self {
-        match value {
-            BlobExportFormat::Blob => iroh::blobs::store::ExportFormat::Blob,
-            BlobExportFormat::Collection => iroh::blobs::store::ExportFormat::Collection,
-        }
+    /// The name of `path` below `root`, relative and joined with `/`, as matched by the
+    /// include and exclude patterns.
+    fn name(root: &std::path::Path, path: &std::path::Path) -> anyhow::Result<String> {
+        let rel = path.strip_prefix(root)?;
+        iroh::util::fs::canonicalized_path_to_string(rel, true)
     }
-}
 
-/// The export mode describes how files will be exported.
-///
+    /// Walk `root`, returning the relative name, absolute path and size of every
+    /// selected file, sorted by name.
+    pub(crate) fn scan(
+        &self,
+        root: &std::path::Path,
+    ) -> anyhow::Result<Vec<(String, PathBuf, u64)>> {
+        let walker = walkdir::WalkDir::new(root)
+            .follow_links(self.symlinks == SymlinkPolicy::Follow)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                if entry.depth() == 0 {
+                    return true;
+                }
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                if hidden && !self.include_hidden {
+                    return false;
+                }
+                // names that can not be converted are kept, scanning the files below fails
+                match Self::name(root, entry.path()) {
+                    Ok(name) => !Self::matches(&self.exclude, &name),
+                    Err(_) => true,
+                }
+            });
+
+        let mut files = Vec::new();
+        for entry in walker {
+            let entry = entry?;
+            if entry.path_is_symlink() && self.symlinks != SymlinkPolicy::Follow {
+                match self.symlinks {
+                    SymlinkPolicy::Error => {
+                        anyhow::bail!("found symbolic link {}", entry.path().display())
+                    }
+                    _ => continue,
+                }
+            }
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let name = Self::name(root, entry.path())?;
+            if !self.include.is_empty() && !Self::matches(&self.include, &name) {
+                continue;
+            }
+            let size = entry.metadata()?.len();
+            files.push((name, entry.path().to_owned(), size));
+        }
+        Ok(files)
+    }
+}
+
+/// The `progress` method will be called for each `AddDirectoryProgress` event that is
+/// emitted during a `node.blobs_add_directory`. Use the `AddDirectoryProgress.type()`
+/// method to check the `AddDirectoryProgressType`
+#[uniffi::export(with_foreign)]
+#[async_trait::async_trait]
+pub trait AddDirectoryCallback: Send + Sync + 'static {
+    async fn progress(&self, progress: Arc<AddDirectoryProgress>) -> Result<(), CallbackError>;
+}
+
+/// The different types of AddDirectoryProgress events
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, uniffi::Enum)]
+pub enum AddDirectoryProgressType {
+    /// A file was found at `path`, and is about to be imported.
+    Found,
+    /// We are done with the file at `path`, and the hash is `hash`.
+    Done,
+    /// We got an error and need to abort.
+    ///
+    /// This will be the last message.
+    Abort,
+}
+
+/// An AddDirectoryProgress event indicating a file was found at `path`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct AddDirectoryProgressFound {
+    /// The path of the file, relative to the imported directory.
+    pub path: String,
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+/// An AddDirectoryProgress event indicating the file at `path` was imported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct AddDirectoryProgressDone {
+    /// The path of the file, relative to the imported directory.
+    pub path: String,
+    /// The hash of the file.
+    pub hash: Arc<Hash>,
+}
+
+/// An AddDirectoryProgress event indicating we got an error and need to abort
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct AddDirectoryProgressAbort {
+    /// The path of the file that failed, if the error is specific to a file.
+    pub path: Option<String>,
+    /// The error message
+    pub error: String,
+}
+
+/// Progress updates for the add directory operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Object)]
+pub enum AddDirectoryProgress {
+    /// A file was found at `path`, and is about to be imported.
+    Found(AddDirectoryProgressFound),
+    /// We are done with the file at `path`, and the hash is `hash`.
+    Done(AddDirectoryProgressDone),
+    /// We got an error and need to abort.
+    ///
+    /// This will be the last message.
+    Abort(AddDirectoryProgressAbort),
+}
+
+#[uniffi::export]
+impl AddDirectoryProgress {
+    /// Get the type of event
+    pub fn r#type(&self) -> AddDirectoryProgressType {
+        match self {
+            AddDirectoryProgress::Found(_) => AddDirectoryProgressType::Found,
+            AddDirectoryProgress::Done(_) => AddDirectoryProgressType::Done,
+            AddDirectoryProgress::Abort(_) => AddDirectoryProgressType::Abort,
+        }
+    }
+    /// Return the `AddDirectoryProgressFound` event
+    pub fn as_found(&self) -> AddDirectoryProgressFound {
+        match self {
+            AddDirectoryProgress::Found(f) => f.clone(),
+            _ => panic!("AddDirectoryProgress type is not 'Found'"),
+        }
+    }
+
+    /// Return the `AddDirectoryProgressDone` event
+    pub fn as_done(&self) -> AddDirectoryProgressDone {
+        match self {
+            AddDirectoryProgress::Done(d) => d.clone(),
+            _ => panic!("AddDirectoryProgress type is not 'Done'"),
+        }
+    }
+
+    /// Return the `AddDirectoryProgressAbort`
+    pub fn as_abort(&self) -> AddDirectoryProgressAbort {
+        match self {
+            AddDirectoryProgress::Abort(a) => a.clone(),
+            _ => panic!("AddDirectoryProgress type is not 'Abort'"),
+        }
+    }
+}
+
+/// The `progress` method will be called for each `BlobValidateProgress` event that is
+/// emitted during a `node.blobs_validate`. Use the `BlobValidateProgress.type()`
+/// method to check the `BlobValidateProgressType`
+#[uniffi::export(with_foreign)]
+#[async_trait::async_trait]
+pub trait BlobValidateCallback: Send + Sync + 'static {
+    async fn progress(&self, progress: Arc<BlobValidateProgress>) -> Result<(), CallbackError>;
+}
+
+/// The status of a blob, as found by `node.blobs_validate`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+pub enum BlobValidateStatus {
+    /// The blob is complete, and all of its data matches its hash.
+    Complete,
+    /// The blob is partially stored, and the stored data matches its hash.
+    Partial,
+    /// The stored data does not match the hash, or could not be read.
+    Corrupt,
+    /// The blob is referenced by a tag or a tagged hash sequence, but not in the store.
+    Missing,
+}
+
+/// The different types of BlobValidateProgress events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+pub enum BlobValidateProgressType {
+    /// Started validating the blobs of the store.
+    Starting,
+    /// A blob was checked.
+    Entry,
+    /// A problem with the store itself was found.
+    Warning,
+    /// We are done with the whole operation.
+    AllDone,
+}
+
+/// A BlobValidateProgress event indicating we started validating the blobs of the store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct BlobValidateStarting {
+    /// The number of complete and partial blobs to validate.
+    pub total: u64,
+}
+
+/// A BlobValidateProgress event indicating a blob was checked
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct BlobValidateEntry {
+    /// The hash of the blob.
+    pub hash: Arc<Hash>,
+    /// The size of the blob in bytes, or the best known size for a partial blob.
+    ///
+    /// Zero for missing blobs.
+    pub size: u64,
+    /// The status of the blob.
+    pub status: BlobValidateStatus,
+    /// Why the blob is corrupt.
+    pub error: Option<String>,
+    /// Whether the blob was dropped from the store.
+    ///
+    /// Persistent nodes do not drop blobs that were added or downloaded since the last garbage
+    /// collection started, run the garbage collection and validate again to drop those.
+    pub dropped: bool,
+}
+
+/// A BlobValidateProgress event indicating a problem with the store itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct BlobValidateWarning {
+    /// The blob the problem is about, if any.
+    pub hash: Option<Arc<Hash>>,
+    /// The warning message
+    pub message: String,
+}
+
+/// A BlobValidateProgress event indicating we are done, with the number of blobs per status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct BlobValidateAllDone {
+    /// The number of complete blobs.
+    pub complete: u64,
+    /// The number of partial blobs.
+    pub partial: u64,
+    /// The number of corrupt blobs.
+    pub corrupt: u64,
+    /// The number of missing blobs.
+    pub missing: u64,
+    /// The number of corrupt blobs that were dropped from the store.
+    pub dropped: u64,
+}
+
+/// Progress updates for the validate operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Object)]
+pub enum BlobValidateProgress {
+    /// Started validating the blobs of the store.
+    Starting(BlobValidateStarting),
+    /// A blob was checked.
+    Entry(BlobValidateEntry),
+    /// A problem with the store itself was found.
+    Warning(BlobValidateWarning),
+    /// We are done with the whole operation.
+    ///
+    /// This will be the last message.
+    AllDone(BlobValidateAllDone),
+}
+
+#[uniffi::export]
+impl BlobValidateProgress {
+    /// Get the type of event
+    pub fn r#type(&self) -> BlobValidateProgressType {
+        match self {
+            BlobValidateProgress::Starting(_) => BlobValidateProgressType::Starting,
+            BlobValidateProgress::Entry(_) => BlobValidateProgressType::Entry,
+            BlobValidateProgress::Warning(_) => BlobValidateProgressType::Warning,
+            BlobValidateProgress::AllDone(_) => BlobValidateProgressType::AllDone,
+        }
+    }
+    /// Return the `BlobValidateStarting` event
+    pub fn as_starting(&self) -> BlobValidateStarting {
+        match self {
+            BlobValidateProgress::Starting(s) => s.clone(),
+            _ => panic!("BlobValidateProgress type is not 'Starting'"),
+        }
+    }
+    /// Return the `BlobValidateEntry` event
+    pub fn as_entry(&self) -> BlobValidateEntry {
+        match self {
+            BlobValidateProgress::Entry(e) => e.clone(),
+            _ => panic!("BlobValidateProgress type is not 'Entry'"),
+        }
+    }
+    /// Return the `BlobValidateWarning` event
+    pub fn as_warning(&self) -> BlobValidateWarning {
+        match self {
+            BlobValidateProgress::Warning(w) => w.clone(),
+            _ => panic!("BlobValidateProgress type is not 'Warning'"),
+        }
+    }
+    /// Return the `BlobValidateAllDone` event
+    pub fn as_all_done(&self) -> BlobValidateAllDone {
+        match self {
+            BlobValidateProgress::AllDone(a) => a.clone(),
+            _ => panic!("BlobValidateProgress type is not 'AllDone'"),
+        }
+    }
+}
+
+/// Storage usage of the blob store, as returned by `node.blobs_store_stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct StoreStats {
+    /// The number of complete blobs.
+    pub complete_count: u64,
+    /// The total size of the complete blobs, in bytes.
+    pub complete_bytes: u64,
+    /// The number of partial blobs.
+    pub partial_count: u64,
+    /// The bytes stored for partial blobs.
+    ///
+    /// For in-memory nodes, this is the best known size of the partial blobs.
+    pub partial_bytes: u64,
+    /// The number of complete blobs small enough to be stored inline in the database.
+    ///
+    /// These have no file of their own, on persistent nodes they are part of the `blobs.db`
+    /// file of the store.
+    pub inline_count: u64,
+    /// The total size of the blobs stored inline, in bytes.
+    pub inline_bytes: u64,
+    /// The number of complete blobs stored in files owned by the store, the
+    /// `data/<hash>.data` files below its directory.
+    pub owned_file_count: u64,
+    /// The total size of the `data/<hash>.data` files of complete blobs, in bytes.
+    pub owned_file_bytes: u64,
+    /// The number of complete blobs imported in place, referencing files outside the store.
+    pub reference_count: u64,
+    /// The total size of the referenced files outside the store, in bytes.
+    ///
+    /// These files are owned by the user, not by the store.
+    pub reference_bytes: u64,
+    /// The number of outboards, of complete and partial blobs.
+    ///
+    /// Blobs of up to 16 KiB do not need an outboard. Larger outboards are stored in
+    /// `data/<hash>.obao4` files below the directory of the store.
+    pub outboard_count: u64,
+    /// The total size of the outboards, inline and in `data/<hash>.obao4` files, in bytes.
+    pub outboard_bytes: u64,
+    /// The number of outboards small enough to be stored inline, included in `outboard_count`.
+    pub inline_outboard_count: u64,
+    /// The total size of the outboards stored inline, in bytes.
+    pub inline_outboard_bytes: u64,
+    /// The number of complete blobs whose data the store can not find or read, e.g. because
+    /// a file was deleted or truncated. Run `node.blobs_validate` to check them.
+    pub unknown_count: u64,
+    /// The total size of the blobs whose data the store can not find or read, in bytes.
+    pub unknown_bytes: u64,
+}
+
+impl StoreStats {
+    fn add_outboard(&mut self, outboard: Location) {
+        match outboard {
+            Location::None => {}
+            Location::Inline(len) => {
+                self.inline_outboard_count += 1;
+                self.inline_outboard_bytes += len;
+                self.outboard_count += 1;
+                self.outboard_bytes += len;
+            }
+            Location::Owned(len) | Location::Reference(len) | Location::Unknown(len) => {
+                self.outboard_count += 1;
+                self.outboard_bytes += len;
+            }
+        }
+    }
+}
+
+/// A format identifier
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+pub enum BlobFormat {
+    /// Raw blob
+    Raw,
+    /// A sequence of BLAKE3 hashes
+    HashSeq,
+}
+
+impl From<iroh::blobs::BlobFormat> for BlobFormat {
+    fn from(value: iroh::blobs::BlobFormat) -> Self {
+        match value {
+            iroh::blobs::BlobFormat::Raw => BlobFormat::Raw,
+            iroh::blobs::BlobFormat::HashSeq => BlobFormat::HashSeq,
+        }
+    }
+}
+
+impl From<BlobFormat> for iroh::blobs::BlobFormat {
+    fn from(value: BlobFormat) -> Self {
+        match value {
+            BlobFormat::Raw => iroh::blobs::BlobFormat::Raw,
+            BlobFormat::HashSeq => iroh::blobs::BlobFormat::HashSeq,
+        }
+    }
+}
+
+/// Options to download  data specified by the hash.
+#[derive(Debug, uniffi::Object)]
+pub struct BlobDownloadOptions {
+    pub(crate) opts: iroh::client::blobs::DownloadOptions,
+    /// Ranges to fetch: for a raw blob a single entry, for a hash seq one entry per child.
+    ///
+    /// `None` downloads everything.
+    pub(crate) ranges: Option<Vec<ChunkRanges>>,
+    /// Fetch a hash seq before its children, so children complete locally are not requested.
+    pub(crate) skip_complete: bool,
+}
+
+#[uniffi::export]
+impl BlobDownloadOptions {
+    /// Create a BlobDownloadRequest
+    #[uniffi::constructor]
+    pub fn new(
+        format: BlobFormat,
+        nodes: Vec<Arc<NodeAddr>>,
+        tag: Arc<SetTagOption>,
+    ) -> Result<Self, IrohError> {
+        Self::with_mode(format, nodes, tag, DownloadMode::Direct)
+    }
+
+    /// Create a BlobDownloadRequest, using the given download mode.
+    #[uniffi::constructor]
+    pub fn with_mode(
+        format: BlobFormat,
+        nodes: Vec<Arc<NodeAddr>>,
+        tag: Arc<SetTagOption>,
+        mode: DownloadMode,
+    ) -> Result<Self, IrohError> {
+        Ok(iroh::client::blobs::DownloadOptions {
+            format: format.into(),
+            nodes: node_addrs(nodes)?,
+            tag: (*tag).clone().into(),
+            mode: mode.into(),
+        }
+        .into())
+    }
+
+    /// The download mode.
+    pub fn mode(&self) -> DownloadMode {
+        match self.opts.mode {
+            iroh::client::blobs::DownloadMode::Direct => DownloadMode::Direct,
+            iroh::client::blobs::DownloadMode::Queued => DownloadMode::Queued,
+        }
+    }
+
+    /// Create a BlobDownloadRequest that only fetches the given ranges.
+    ///
+    /// For [`BlobFormat::Raw`], `ranges` must contain a single entry, selecting the
+    /// ranges of the blob to fetch.
+    ///
+    /// For [`BlobFormat::HashSeq`], the hash sequence itself is always fetched completely,
+    /// and `ranges[i]` selects the ranges to fetch of the child at index `i`. Children
+    /// past the end of `ranges` are not fetched.
+    ///
+    /// Partially downloaded blobs are only kept by persistent nodes, a node created with
+    /// `Iroh::memory` drops the data of blobs that are not complete. Persistent nodes also
+    /// only keep partial blobs once more than 16 KiB of them has been downloaded.
+    #[uniffi::constructor]
+    pub fn with_ranges(
+        format: BlobFormat,
+        nodes: Vec<Arc<NodeAddr>>,
+        tag: Arc<SetTagOption>,
+        ranges: Vec<Arc<RangeSpec>>,
+    ) -> Result<Self, IrohError> {
+        if matches!(format, BlobFormat::Raw) && ranges.len() != 1 {
+            return Err(anyhow::anyhow!(
+                "expected a single range spec for a raw blob, got {}",
+                ranges.len()
+            )
+            .into());
+        }
+        let mut opts = Self::new(format, nodes, tag)?;
+        opts.ranges = Some(ranges.iter().map(|r| r.0.to_chunk_ranges()).collect());
+        opts.skip_complete = true;
+        Ok(opts)
+    }
+
+    /// Create a BlobDownloadRequest that only fetches data missing locally.
+    ///
+    /// For [`BlobFormat::HashSeq`], if the hash sequence itself is not available locally it
+    /// is fetched first, and then only the children that are not complete locally are
+    /// requested. Use this to re-download a changed collection without transferring the
+    /// entries that did not change.
+    #[uniffi::constructor]
+    pub fn incremental(
+        format: BlobFormat,
+        nodes: Vec<Arc<NodeAddr>>,
+        tag: Arc<SetTagOption>,
+    ) -> Result<Self, IrohError> {
+        let mut opts = Self::new(format, nodes, tag)?;
+        opts.skip_complete = true;
+        Ok(opts)
+    }
+}
+
+impl From<iroh::client::blobs::DownloadOptions> for BlobDownloadOptions {
+    fn from(value: iroh::client::blobs::DownloadOptions) -> Self {
+        BlobDownloadOptions {
+            opts: value,
+            ranges: None,
+            skip_complete: false,
+        }
+    }
+}
+
+/// Convert FFI node addresses to iroh node addresses.
+fn node_addrs(nodes: Vec<Arc<NodeAddr>>) -> Result<Vec<iroh::net::NodeAddr>, IrohError> {
+    nodes
+        .into_iter()
+        .map(|node| (*node).clone().try_into())
+        .collect()
+}
+
+/// Set the mode for whether to directly start the download or add it to the download queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum DownloadMode {
+    /// Start the download right away.
+    ///
+    /// No concurrency limits or queuing will be applied. It is up to the user to manage download
+    /// concurrency.
+    Direct,
+    /// Queue the download.
+    ///
+    /// The download queue will be processed in-order, while respecting the downloader concurrency
+    /// limits. Requests for the same content are merged, and the providers of all of them are
+    /// tried, with failed transfers retried from the remaining providers.
+    Queued,
+}
+
+impl From<DownloadMode> for iroh::client::blobs::DownloadMode {
+    fn from(value: DownloadMode) -> Self {
+        match value {
+            DownloadMode::Direct => iroh::client::blobs::DownloadMode::Direct,
+            DownloadMode::Queued => iroh::client::blobs::DownloadMode::Queued,
+        }
+    }
+}
+
+/// The expected format of a hash being exported.
+#[derive(Debug, uniffi::Enum)]
+pub enum BlobExportFormat {
+    /// The hash refers to any blob and will be exported to a single file.
+    Blob,
+    /// The hash refers to a [`crate::format::collection::Collection`] blob
+    /// and all children of the collection shall be exported to one file per child.
+    ///
+    /// If the blob can be parsed as a [`BlobFormat::HashSeq`], and the first child contains
+    /// collection metadata, all other children of the collection will be exported to
+    /// a file each, with their collection name treated as a relative path to the export
+    /// destination path.
+    ///
+    /// If the blob cannot be parsed as a collection, the operation will fail.
+    ///
+    /// Nested collections are not expanded, use [`Blobs::export_tree`] to export them.
+    Collection,
+}
+
+impl From<BlobExportFormat> for iroh::blobs::store::ExportFormat {
+    fn from(value: BlobExportFormat) -> Self {
+        match value {
+            BlobExportFormat::Blob => iroh::blobs::store::ExportFormat::Blob,
+            BlobExportFormat::Collection => iroh::blobs::store::ExportFormat::Collection,
+        }
+    }
+}
+
+/// The export mode describes how files will be exported.
+///
 /// This is a hint to the import trait method. For some implementations, this
 /// does not make any sense. E.g. an in memory implementation will always have
 /// to copy the file into memory. Also, a disk based implementation might choose
@@ -1436,12 +3195,274 @@ impl DownloadProgress {
     }
 }
 
-/// A chunk range specification as a sequence of chunk offsets
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Object)]
-pub struct RangeSpec(pub(crate) iroh::blobs::protocol::RangeSpec);
+/// Protection of a blob from garbage collection for a bounded time, created via
+/// [`Blobs::protect`].
+///
+/// Dropping the lease releases it, so keep a reference for as long as the data is needed.
+#[derive(uniffi::Object)]
+pub struct BlobLease(Arc<LeaseInner>);
+
+impl Drop for BlobLease {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+struct LeaseInner {
+    state: std::sync::Mutex<LeaseState>,
+    /// Notified when the lease is renewed or released.
+    changed: tokio::sync::Notify,
+}
+
+struct LeaseState {
+    deadline: tokio::time::Instant,
+    /// The temp tag protecting the data, and the batch it belongs to. `None` once the lease
+    /// has ended.
+    held: Option<(iroh::client::blobs::Batch, iroh::blobs::TempTag)>,
+}
+
+/// Release the lease once its deadline has passed.
+async fn expire_lease(lease: Arc<LeaseInner>) {
+    loop {
+        let changed = lease.changed.notified();
+        let deadline = {
+            let mut state = lease.state.lock().unwrap();
+            if state.held.is_none() {
+                return;
+            }
+            if tokio::time::Instant::now() >= state.deadline {
+                state.held = None;
+                return;
+            }
+            state.deadline
+        };
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => {}
+            _ = changed => {}
+        }
+    }
+}
+
+#[uniffi::export]
+impl BlobLease {
+    /// Extend the lease to end `duration` from now.
+    ///
+    /// Fails if the lease has already expired or was released.
+    pub fn renew(&self, duration: Duration) -> Result<(), IrohError> {
+        let mut state = self.0.state.lock().unwrap();
+        if state.held.is_none() {
+            return Err(anyhow::anyhow!("lease has already ended").into());
+        }
+        state.deadline = tokio::time::Instant::now() + duration;
+        self.0.changed.notify_one();
+        Ok(())
+    }
+
+    /// End the lease, allowing the data to be garbage collected unless it is otherwise
+    /// protected.
+    pub fn release(&self) {
+        self.0.state.lock().unwrap().held = None;
+        self.0.changed.notify_one();
+    }
+
+    /// Whether the lease is still protecting the data.
+    pub fn is_active(&self) -> bool {
+        self.0.state.lock().unwrap().held.is_some()
+    }
+}
+
+/// A handle to a running download, created via [`Blobs::download_start`].
+#[derive(uniffi::Object)]
+pub struct DownloadHandle {
+    node: Iroh,
+    hash: iroh::blobs::Hash,
+    opts: iroh::client::blobs::DownloadOptions,
+    cancel: CancellationToken,
+    state: Arc<std::sync::Mutex<DownloadState>>,
+    done: Shared<BoxFuture<'static, Result<DownloadProgressAllDone, DownloadError>>>,
+}
+
+#[uniffi::export]
+impl DownloadHandle {
+    /// Cancel the download.
+    ///
+    /// [`Self::await_done`] will return [`DownloadError::Cancelled`], unless the download
+    /// already finished.
+    ///
+    /// For downloads started with [`DownloadMode::Queued`] this only detaches from the download:
+    /// iroh does not offer a way to cancel a request queued with the node's downloader, so it
+    /// keeps fetching the data in the background, and tags it once complete.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Wait for the download to finish.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn await_done(&self) -> Result<DownloadProgressAllDone, DownloadError> {
+        self.done.clone().await
+    }
+
+    /// The current progress of the download.
+    pub fn progress_snapshot(&self) -> DownloadProgressSnapshot {
+        self.state.lock().unwrap().snapshot.clone()
+    }
+
+    /// Add more candidate providers to the download.
+    ///
+    /// Only supported for downloads started with [`DownloadMode::Queued`]. The node's downloader
+    /// merges this with the running download, and tries the new providers if the current ones
+    /// fail or drop.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn add_providers(&self, nodes: Vec<Arc<NodeAddr>>) -> Result<(), IrohError> {
+        if !matches!(self.opts.mode, iroh::client::blobs::DownloadMode::Queued) {
+            return Err(anyhow::anyhow!("providers can only be added to queued downloads").into());
+        }
+        // Automatic tags are resolved to a name when the download starts, so this sets the
+        // tag of the original request again, instead of creating a second one.
+        let opts = iroh::client::blobs::DownloadOptions {
+            nodes: node_addrs(nodes)?,
+            ..self.opts.clone()
+        };
+        let client = self.node.inner_client().clone();
+        let mut stream = client.blobs().download_with_opts(self.hash, opts).await?;
+        // Keep the request alive until the download finishes, its progress is reported
+        // through the original request.
+        let cancel = self.cancel.clone();
+        tokio::task::spawn(async move {
+            tokio::select! {
+                biased;
+
+                _ = cancel.cancelled() => {}
+                _ = async { while stream.next().await.is_some() {} } => {}
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Error returned by [`DownloadHandle::await_done`].
+#[derive(Debug, Clone, thiserror::Error, uniffi::Error)]
+pub enum DownloadError {
+    /// The download was cancelled via [`DownloadHandle::cancel`].
+    #[error("download cancelled")]
+    Cancelled,
+    /// The download failed.
+    #[error("{message}")]
+    Failed { message: String },
+}
+
+impl From<anyhow::Error> for DownloadError {
+    fn from(e: anyhow::Error) -> Self {
+        DownloadError::Failed {
+            message: format!("{e:?}"),
+        }
+    }
+}
+
+/// The progress of a download at a point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, uniffi::Record)]
+pub struct DownloadProgressSnapshot {
+    /// Whether a connection to a provider was established
+    pub connected: bool,
+    /// The number of blobs that are being downloaded
+    pub blobs_found: u64,
+    /// The number of blobs that finished downloading
+    pub blobs_done: u64,
+    /// The total size of the blobs that are being downloaded
+    pub total_size: u64,
+    /// The number of bytes downloaded so far
+    pub bytes_downloaded: u64,
+    /// Whether the download finished successfully
+    pub finished: bool,
+}
+
+/// Tracks the progress of a download, see [`DownloadHandle::progress_snapshot`].
+#[derive(Debug, Default)]
+struct DownloadState {
+    snapshot: DownloadProgressSnapshot,
+    /// Size of each blob, by progress id
+    sizes: HashMap<u64, u64>,
+    /// Offset reached for each blob, by progress id
+    offsets: HashMap<u64, u64>,
+}
+
+impl DownloadState {
+    fn update(&mut self, progress: &DownloadProgress) {
+        let snapshot = &mut self.snapshot;
+        match progress {
+            DownloadProgress::InitialState(state) => snapshot.connected = state.connected,
+            DownloadProgress::Connected => snapshot.connected = true,
+            DownloadProgress::Found(found) => {
+                // the downloader of the node does not report `Connected`, but data can only
+                // be found on a provider once connected
+                snapshot.connected = true;
+                snapshot.blobs_found += 1;
+                snapshot.total_size += found.size;
+                self.sizes.insert(found.id, found.size);
+            }
+            DownloadProgress::Progress(p) => self.advance(p.id, p.offset),
+            DownloadProgress::Done(done) => {
+                self.snapshot.blobs_done += 1;
+                let size = self.sizes.get(&done.id).copied().unwrap_or_default();
+                self.advance(done.id, size);
+            }
+            DownloadProgress::AllDone(_) => snapshot.finished = true,
+            _ => {}
+        }
+    }
+
+    fn advance(&mut self, id: u64, offset: u64) {
+        let previous = self.offsets.insert(id, offset).unwrap_or_default();
+        self.snapshot.bytes_downloaded += offset.saturating_sub(previous);
+    }
+}
+
+/// A chunk range specification as a sequence of chunk offsets
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Object)]
+pub struct RangeSpec(pub(crate) iroh::blobs::protocol::RangeSpec);
+
+#[uniffi::export]
+impl RangeSpec {
+    /// A [`RangeSpec`] selecting the entire blob.
+    #[uniffi::constructor]
+    pub fn all() -> Self {
+        RangeSpec(iroh::blobs::protocol::RangeSpec::all())
+    }
+
+    /// A [`RangeSpec`] selecting nothing from the blob.
+    #[uniffi::constructor]
+    pub fn empty() -> Self {
+        RangeSpec(iroh::blobs::protocol::RangeSpec::EMPTY)
+    }
+
+    /// A [`RangeSpec`] selecting the given byte ranges.
+    ///
+    /// Data is verified and transferred in chunks of 1024 bytes, so each range is widened to
+    /// the chunks containing it.
+    #[uniffi::constructor]
+    pub fn from_byte_ranges(ranges: Vec<ByteRange>) -> Self {
+        let ranges =
+            ranges
+                .into_iter()
+                .filter(|r| r.start < r.end)
+                .fold(ChunkRanges::empty(), |acc, r| {
+                    acc | ChunkRanges::from(ChunkNum::full_chunks(r.start)..ChunkNum::chunks(r.end))
+                });
+        RangeSpec(iroh::blobs::protocol::RangeSpec::new(ranges))
+    }
+
+    /// A [`RangeSpec`] selecting the given ranges of 1024 byte chunks.
+    #[uniffi::constructor]
+    pub fn from_chunk_ranges(ranges: Vec<ChunkRange>) -> Self {
+        let ranges = ranges
+            .into_iter()
+            .filter(|r| r.start < r.end)
+            .fold(ChunkRanges::empty(), |acc, r| {
+                acc | ChunkRanges::from(ChunkNum(r.start)..ChunkNum(r.end))
+            });
+        RangeSpec(iroh::blobs::protocol::RangeSpec::new(ranges))
+    }
 
-#[uniffi::export]
-impl RangeSpec {
     /// Checks if this [`RangeSpec`] does not select any chunks in the blob
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
@@ -1459,6 +3480,24 @@ impl From<iroh::blobs::protocol::RangeSpec> for RangeSpec {
     }
 }
 
+/// A range of bytes, from `start` (inclusive) to `end` (exclusive).
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ByteRange {
+    /// The first byte of the range
+    pub start: u64,
+    /// The end of the range, exclusive
+    pub end: u64,
+}
+
+/// A range of 1024 byte chunks, from `start` (inclusive) to `end` (exclusive).
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ChunkRange {
+    /// The first chunk of the range
+    pub start: u64,
+    /// The end of the range, exclusive
+    pub end: u64,
+}
+
 /// A response to a list blobs request
 #[derive(Debug, Clone, uniffi::Record)]
 pub struct BlobInfo {
@@ -1468,15 +3507,22 @@ pub struct BlobInfo {
     pub hash: Arc<Hash>,
     /// The size of the blob
     pub size: u64,
+    /// The names of the tags that reference this blob directly
+    pub tags: Vec<Vec<u8>>,
 }
 
-impl From<iroh::client::blobs::BlobInfo> for BlobInfo {
-    fn from(value: iroh::client::blobs::BlobInfo) -> Self {
-        BlobInfo {
+impl BlobInfo {
+    async fn new(
+        value: iroh::client::blobs::BlobInfo,
+        tags: Arc<TagIndex>,
+    ) -> anyhow::Result<Self> {
+        let tags = tags.tags_for(&value.hash).await;
+        Ok(BlobInfo {
             path: value.path,
             hash: Arc::new(value.hash.into()),
             size: value.size,
-        }
+            tags: tags.into_iter().map(|tag| tag.0.to_vec()).collect(),
+        })
     }
 }
 
@@ -1563,6 +3609,34 @@ impl Collection {
         Ok(())
     }
 
+    /// Add a nested collection to this collection.
+    ///
+    /// The entry is stored with a trailing `/` appended to `name`, marking it as a
+    /// collection rather than a file. Nested collections are expanded by
+    /// [`Blobs::get_collection_recursive`], [`Blobs::download_recursive`] and
+    /// [`Blobs::export_tree`].
+    pub fn push_collection(&self, name: String, hash: &Hash) -> Result<(), IrohError> {
+        let name = format!("{}/", name.trim_end_matches('/'));
+        self.0.write().unwrap().push(name, hash.0);
+        Ok(())
+    }
+
+    /// Get the nested collections of this collection, named without the trailing `/`
+    pub fn collections(&self) -> Result<Vec<LinkAndName>, IrohError> {
+        Ok(self
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(name, hash)| {
+                Some(LinkAndName {
+                    name: nested_collection_name(name)?.to_string(),
+                    link: Arc::new(Hash(*hash)),
+                })
+            })
+            .collect())
+    }
+
     /// Check if the collection is empty
     pub fn is_empty(&self) -> Result<bool, IrohError> {
         Ok(self.0.read().unwrap().is_empty())
@@ -1608,6 +3682,204 @@ impl Collection {
     pub fn len(&self) -> Result<u64, IrohError> {
         Ok(self.0.read().unwrap().len() as _)
     }
+
+    /// Get the hash of the blob with the given name
+    pub fn get(&self, name: String) -> Result<Option<Arc<Hash>>, IrohError> {
+        Ok(self
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, hash)| Arc::new(Hash(*hash))))
+    }
+
+    /// Check if the collection contains a blob with the given name
+    pub fn contains(&self, name: String) -> Result<bool, IrohError> {
+        Ok(self.0.read().unwrap().iter().any(|(n, _)| *n == name))
+    }
+
+    /// Remove the blob with the given name from the collection.
+    ///
+    /// Returns the hash of the removed blob, or `None` if there is no blob with this name.
+    pub fn remove(&self, name: String) -> Result<Option<Arc<Hash>>, IrohError> {
+        Ok(self.edit(|blobs| {
+            let index = blobs.iter().position(|(n, _)| *n == name)?;
+            let (_, hash) = blobs.remove(index);
+            Some(Arc::new(Hash(hash)))
+        }))
+    }
+
+    /// Rename the blob named `from` to `to`, keeping its position in the collection.
+    ///
+    /// Fails if there is no blob named `from`, or if there already is another blob named `to`.
+    pub fn rename(&self, from: String, to: String) -> Result<(), IrohError> {
+        self.edit(|blobs| {
+            let index = blobs
+                .iter()
+                .position(|(n, _)| *n == from)
+                .ok_or_else(|| anyhow::anyhow!("collection does not contain {from:?}"))?;
+            if from == to {
+                return Ok(());
+            }
+            if blobs.iter().any(|(n, _)| *n == to) {
+                anyhow::bail!("collection already contains {to:?}");
+            }
+            blobs[index].0 = to;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Insert the given blob at position `index` in the collection, shifting all blobs
+    /// after it.
+    ///
+    /// Fails if `index` is greater than the length of the collection.
+    pub fn insert_at(&self, index: u64, name: String, hash: &Hash) -> Result<(), IrohError> {
+        self.edit(|blobs| {
+            let index = usize::try_from(index)?;
+            let len = blobs.len();
+            anyhow::ensure!(
+                index <= len,
+                "index {index} out of bounds for collection of length {len}",
+            );
+            blobs.insert(index, (name, hash.0));
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Get the hash of the metadata blob attached to this collection, if any.
+    ///
+    /// By convention, the metadata blob is the entry named [`COLLECTION_METADATA_NAME`], so
+    /// it is transferred along with the rest of the collection. The entry is not treated
+    /// specially anywhere else: it is listed, exported and compared like any other entry, and
+    /// a collection from another producer may contain a regular file with this name.
+    pub fn metadata(&self) -> Result<Option<Arc<Hash>>, IrohError> {
+        Ok(self
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(n, _)| is_metadata_name(n))
+            .map(|(_, hash)| Arc::new(Hash(*hash))))
+    }
+
+    /// Attach a metadata blob to this collection, replacing any existing one.
+    ///
+    /// Pass `None` to remove the metadata blob. See [`Collection::metadata`].
+    pub fn set_metadata(&self, hash: Option<Arc<Hash>>) -> Result<(), IrohError> {
+        self.edit(|blobs| {
+            blobs.retain(|(n, _)| !is_metadata_name(n));
+            if let Some(hash) = hash {
+                blobs.insert(0, (COLLECTION_METADATA_NAME.to_string(), hash.0));
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Collection {
+    /// Apply `f` to the entries of the collection.
+    fn edit<T>(&self, f: impl FnOnce(&mut Vec<(String, iroh::blobs::Hash)>) -> T) -> T {
+        let mut collection = self.0.write().unwrap();
+        let mut blobs: Vec<_> = collection.iter().cloned().collect();
+        let res = f(&mut blobs);
+        *collection = blobs.into_iter().collect();
+        res
+    }
+}
+
+/// The name of the entry holding a [`Collection`]'s metadata blob, by convention.
+///
+/// See [`Collection::metadata`].
+pub const COLLECTION_METADATA_NAME: &str = ".collection-metadata";
+
+fn is_metadata_name(name: &str) -> bool {
+    name == COLLECTION_METADATA_NAME
+}
+
+/// If `name` refers to a nested collection, get its name without the trailing `/`.
+fn nested_collection_name(name: &str) -> Option<&str> {
+    name.strip_suffix('/')
+}
+
+/// How deep collections can be nested below the root when expanding a tree.
+const MAX_TREE_DEPTH: usize = 32;
+/// How many entries the collections of a tree can hold in total.
+const MAX_TREE_ENTRIES: usize = 100_000;
+
+/// Fail if a collection tree from a possibly untrusted node grows too deep or too large.
+fn check_tree_limits(depth: usize, entries: usize) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        depth <= MAX_TREE_DEPTH,
+        "collections are nested more than {MAX_TREE_DEPTH} levels deep"
+    );
+    anyhow::ensure!(
+        entries <= MAX_TREE_ENTRIES,
+        "collection tree has more than {MAX_TREE_ENTRIES} entries"
+    );
+    Ok(())
+}
+
+/// The differences between two collections, see [`Blobs::diff_collections`].
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct CollectionDiff {
+    /// Entries only present in the new collection
+    pub added: Vec<LinkAndName>,
+    /// Entries only present in the old collection
+    pub removed: Vec<LinkAndName>,
+    /// Entries present in both collections, with a different hash
+    pub changed: Vec<CollectionChange>,
+}
+
+impl CollectionDiff {
+    fn new(
+        old: &iroh::blobs::format::collection::Collection,
+        new: &iroh::blobs::format::collection::Collection,
+    ) -> Self {
+        let link = |name: &String, hash: &iroh::blobs::Hash| LinkAndName {
+            name: name.clone(),
+            link: Arc::new(Hash(*hash)),
+        };
+        let old_hashes: HashMap<_, _> = old.iter().map(|(name, hash)| (name, hash)).collect();
+        let new_hashes: HashMap<_, _> = new.iter().map(|(name, hash)| (name, hash)).collect();
+
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for (name, hash) in new.iter() {
+            match old_hashes.get(name) {
+                None => added.push(link(name, hash)),
+                Some(old) if *old != hash => changed.push(CollectionChange {
+                    name: name.clone(),
+                    old: Arc::new(Hash(**old)),
+                    new: Arc::new(Hash(*hash)),
+                }),
+                Some(_) => {}
+            }
+        }
+        let removed = old
+            .iter()
+            .filter(|(name, _)| !new_hashes.contains_key(name))
+            .map(|(name, hash)| link(name, hash))
+            .collect();
+        CollectionDiff {
+            added,
+            removed,
+            changed,
+        }
+    }
+}
+
+/// An entry whose hash differs between two collections
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct CollectionChange {
+    /// The name of the entry
+    pub name: String,
+    /// The [`Hash`] in the old collection
+    pub old: Arc<Hash>,
+    /// The [`Hash`] in the new collection
+    pub new: Arc<Hash>,
 }
 
 /// `LinkAndName` includes a name and a hash for a blob in a collection
@@ -1619,65 +3891,622 @@ pub struct LinkAndName {
     pub link: Arc<Hash>,
 }
 
-#[cfg(test)]
-mod tests {
-    use std::io::Write;
-    use std::sync::{Arc, Mutex};
+type DownloadProgressSender = flume::Sender<anyhow::Result<iroh::blobs::get::db::DownloadProgress>>;
 
-    use super::*;
-    use crate::node::Iroh;
-    use crate::{CallbackError, NodeOptions};
-    use bytes::Bytes;
-    use rand::RngCore;
-    use tokio::io::AsyncWriteExt;
-    use tracing_subscriber::FmtSubscriber;
+/// Validate all blobs of `db`, sending a `BlobValidateProgress` for each of them.
+///
+/// Stops when `progress` is dropped.
+async fn validate_store<D: Store>(
+    db: D,
+    repair: bool,
+    progress: flume::Sender<BlobValidateProgress>,
+) -> anyhow::Result<()> {
+    use iroh::blobs::store::EntryStatus;
+
+    let complete = db.blobs().await?.collect::<std::io::Result<Vec<_>>>()?;
+    let partial = db
+        .partial_blobs()
+        .await?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    progress
+        .send_async(BlobValidateProgress::Starting(BlobValidateStarting {
+            total: (complete.len() + partial.len()) as u64,
+        }))
+        .await?;
+
+    let mut done = BlobValidateAllDone {
+        complete: 0,
+        partial: 0,
+        corrupt: 0,
+        missing: 0,
+        dropped: 0,
+    };
+    let entries = complete
+        .into_iter()
+        .map(|hash| (hash, true))
+        .chain(partial.into_iter().map(|hash| (hash, false)));
+    for (hash, is_complete) in entries {
+        let (size, ranges) = match valid_ranges(&db, &hash).await {
+            Ok((size, claimed, valid)) => (size, Ok((claimed, valid))),
+            Err(err) => (0, Err(format!("{err:#}"))),
+        };
+        let (status, error) = match ranges {
+            Ok((claimed, valid)) if claimed.is_subset(&valid) => match is_complete {
+                true => (BlobValidateStatus::Complete, None),
+                false => (BlobValidateStatus::Partial, None),
+            },
+            Ok((claimed, valid)) => (
+                BlobValidateStatus::Corrupt,
+                Some(format!("expected chunk ranges {claimed:?}, got {valid:?}")),
+            ),
+            Err(err) => (BlobValidateStatus::Corrupt, Some(err)),
+        };
+        let mut dropped = false;
+        match status {
+            BlobValidateStatus::Complete => done.complete += 1,
+            BlobValidateStatus::Partial => done.partial += 1,
+            _ => {
+                done.corrupt += 1;
+                if repair {
+                    db.delete(vec![hash]).await?;
+                    // the fs store does not delete blobs imported since the last gc started
+                    dropped = db.entry_status(&hash).await? == EntryStatus::NotFound;
+                }
+            }
+        }
+        done.dropped += dropped as u64;
+        progress
+            .send_async(BlobValidateProgress::Entry(BlobValidateEntry {
+                hash: Arc::new(hash.into()),
+                size,
+                status,
+                error,
+                dropped,
+            }))
+            .await?;
+    }
 
-    #[test]
-    fn test_hash() {
-        let hash_str = "6vp273v6cqbbq7xesa2xfrdt3oajykgeifprn3pj4p6y76654amq";
-        let hex_str = "f55fafeebe1402187ee4903572c473db809c28c4415f16ede9e3fd8ffbdde019";
-        let bytes = b"\xf5\x5f\xaf\xee\xbe\x14\x02\x18\x7e\xe4\x90\x35\x72\xc4\x73\xdb\x80\x9c\x28\xc4\x41\x5f\x16\xed\xe9\xe3\xfd\x8f\xfb\xdd\xe0\x19".to_vec();
+    let mut referenced = std::collections::BTreeSet::new();
+    for item in db.tags().await? {
+        let (_, content) = item?;
+        referenced.insert(content.hash);
+        if content.format.is_hash_seq() {
+            // children of incomplete hash seqs can not be known
+            if let Ok(children) = crate::gc::hash_seq_children(&db, &content.hash).await {
+                referenced.extend(children);
+            }
+        }
+    }
+    for hash in referenced {
+        if db.entry_status(&hash).await? != EntryStatus::NotFound {
+            continue;
+        }
+        done.missing += 1;
+        progress
+            .send_async(BlobValidateProgress::Entry(BlobValidateEntry {
+                hash: Arc::new(hash.into()),
+                size: 0,
+                status: BlobValidateStatus::Missing,
+                error: None,
+                dropped: false,
+            }))
+            .await?;
+    }
 
-        // create hash from string
-        let hash = Hash::from_string(hash_str.into()).unwrap();
+    progress
+        .send_async(BlobValidateProgress::AllDone(done))
+        .await?;
+    Ok(())
+}
 
-        // test methods are as expected
-        assert_eq!(hash_str.to_string(), hash.to_string());
-        assert_eq!(bytes.to_vec(), hash.to_bytes());
-        assert_eq!(hex_str.to_string(), hash.to_hex());
+/// The size of a blob in the store, the chunk ranges the store has for it, and the ranges of
+/// its data that match its outboard.
+///
+/// For partial blobs the store has the ranges that the downloader would not fetch again when
+/// resuming, i.e. those covered by both the data and the outboard.
+async fn valid_ranges<D: Store>(
+    db: &D,
+    hash: &iroh::blobs::Hash,
+) -> anyhow::Result<(u64, ChunkRanges, ChunkRanges)> {
+    use iroh::blobs::IROH_BLOCK_SIZE;
+
+    let entry = db
+        .get_mut(hash)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("entry not found"))?;
+    let size = entry.size().value();
+    let claimed = match entry.is_complete() {
+        true => ChunkRanges::from(..BaoTree::new(size, IROH_BLOCK_SIZE).chunks()),
+        false => iroh::blobs::get::db::valid_ranges::<D>(&entry).await?,
+    };
+    let outboard = entry.outboard().await?;
+    let data = entry.data_reader().await?;
+    let all = ChunkRanges::all();
+    let mut ranges = bao_tree::io::fsm::valid_ranges(outboard, data, &all);
+    let mut valid = ChunkRanges::empty();
+    while let Some(range) = ranges.next().await {
+        valid |= ChunkRanges::from(range?);
+    }
+    Ok((size, claimed, valid))
+}
 
-        // create hash from bytes
-        let hash_0 = Hash::from_bytes(bytes.clone()).unwrap();
+/// Collect the [`StoreStats`] of `db`.
+///
+/// For a persistent store, `blobs_dir` is its directory and [`FsLayout`] locates the data of
+/// each entry. Without it, everything is kept in memory and counted as inline.
+pub(crate) async fn store_stats<D: Store>(
+    db: &D,
+    blobs_dir: Option<&std::path::Path>,
+    pool: &iroh::blobs::util::local_pool::LocalPoolHandle,
+) -> anyhow::Result<StoreStats> {
+    // reading from the store is not `Send`
+    let (db, blobs_dir) = (db.clone(), blobs_dir.map(PathBuf::from));
+    let stats = pool
+        .try_spawn(move || async move { collect_store_stats(&db, blobs_dir.as_deref()).await })?;
+    stats.await?
+}
 
-        // test methods are as expected
-        assert_eq!(hash_str.to_string(), hash_0.to_string());
-        assert_eq!(bytes, hash_0.to_bytes());
-        assert_eq!(hex_str.to_string(), hash_0.to_hex());
+async fn collect_store_stats<D: Store>(
+    db: &D,
+    blobs_dir: Option<&std::path::Path>,
+) -> anyhow::Result<StoreStats> {
+    use iroh::blobs::IROH_BLOCK_SIZE;
 
-        // test that the eq function works
-        assert!(hash.equal(&hash_0));
-        assert!(hash_0.equal(&hash));
-    }
+    let layout = blobs_dir.map(FsLayout::new);
+    let mut stats = StoreStats::default();
 
-    #[tokio::test]
-    async fn test_blobs_add_get_bytes() {
-        let dir = tempfile::tempdir().unwrap();
-        let node = Iroh::persistent(dir.into_path().display().to_string())
-            .await
-            .unwrap();
+    for hash in db.blobs().await? {
+        let hash = hash?;
+        let Some(entry) = db.get(&hash).await? else {
+            continue;
+        };
+        let size = entry.size().value();
+        stats.complete_count += 1;
+        stats.complete_bytes += size;
+        let (data, outboard) = match layout {
+            Some(ref layout) => layout.complete(&entry).await?,
+            None => {
+                let outboard = BaoTree::new(size, IROH_BLOCK_SIZE).outboard_size();
+                let outboard = match outboard {
+                    0 => Location::None,
+                    len => Location::Inline(len),
+                };
+                (Location::Inline(size), outboard)
+            }
+        };
+        match data {
+            Location::None => {}
+            Location::Inline(len) => {
+                stats.inline_count += 1;
+                stats.inline_bytes += len;
+            }
+            Location::Owned(len) => {
+                stats.owned_file_count += 1;
+                stats.owned_file_bytes += len;
+            }
+            Location::Reference(len) => {
+                stats.reference_count += 1;
+                stats.reference_bytes += len;
+            }
+            Location::Unknown(len) => {
+                stats.unknown_count += 1;
+                stats.unknown_bytes += len;
+            }
+        }
+        stats.add_outboard(outboard);
+    }
 
-        let sizes = [1, 10, 100, 1000, 10000, 100000];
-        let mut hashes = Vec::new();
-        for size in sizes.iter() {
-            let hash = blobs_add_get_bytes_size(&node, *size);
-            hashes.push(hash)
+    for hash in db.partial_blobs().await? {
+        let hash = hash?;
+        stats.partial_count += 1;
+        let Some(ref layout) = layout else {
+            if let Some(entry) = db.get(&hash).await? {
+                stats.partial_bytes += entry.size().value();
+            }
+            continue;
+        };
+        let (data, outboard) = layout.partial(&hash).await?;
+        if let Location::Owned(len) = data {
+            stats.partial_bytes += len;
         }
+        stats.add_outboard(outboard);
     }
+    Ok(stats)
+}
 
-    async fn blobs_add_get_bytes_size(node: &Iroh, size: usize) -> Arc<Hash> {
-        // create bytes
-        let mut bytes = vec![0; size];
-        rand::thread_rng().fill_bytes(&mut bytes);
+/// Download the given `ranges` of a blob or hash sequence into the store, verifying the data
+/// as it arrives.
+///
+/// Progress is reported through `progress`. Errors are reported as the last item.
+async fn download_ranges<D: Store>(
+    db: D,
+    endpoint: iroh::net::Endpoint,
+    hash: iroh::blobs::Hash,
+    opts: iroh::client::blobs::DownloadOptions,
+    ranges: Option<Vec<ChunkRanges>>,
+    progress: DownloadProgressSender,
+) {
+    if let Err(err) = download_ranges0(db, endpoint, hash, opts, ranges, &progress).await {
+        progress.send_async(Err(err)).await.ok();
+    }
+}
+
+async fn download_ranges0<D: Store>(
+    db: D,
+    endpoint: iroh::net::Endpoint,
+    hash: iroh::blobs::Hash,
+    opts: iroh::client::blobs::DownloadOptions,
+    ranges: Option<Vec<ChunkRanges>>,
+    progress: &DownloadProgressSender,
+) -> anyhow::Result<()> {
+    use iroh::blobs::{
+        get::{
+            db::{blob_info, BlobId, DownloadProgress},
+            fsm::{self, ConnectedNext, EndBlobNext},
+        },
+        protocol::{GetRequest, RangeSpecSeq},
+        util::SetTagOption,
+        BlobFormat, HashAndFormat,
+    };
+
+    let hash_and_format = HashAndFormat {
+        hash,
+        format: opts.format,
+    };
+    // protect the data from gc while we download it
+    let _temp_tag = db.temp_tag(hash_and_format);
+
+    let wanted = |index: usize| match &ranges {
+        Some(ranges) => ranges
+            .get(index)
+            .cloned()
+            .unwrap_or_else(ChunkRanges::empty),
+        None => ChunkRanges::all(),
+    };
+    let mut conn = None;
+    let mut stats = iroh::blobs::get::Stats::default();
+    let mut next_id = 0;
+
+    let children = match opts.format {
+        BlobFormat::Raw => None,
+        BlobFormat::HashSeq => {
+            let children = match local_hash_seq(&db, &hash).await? {
+                Some(children) => children,
+                None => {
+                    // fetch the hash seq on its own first, so that children which are
+                    // already complete locally are not requested again
+                    let conn = connect_once(&mut conn, &endpoint, &opts.nodes, progress).await?;
+                    let request = GetRequest::single(hash);
+                    let connected = fsm::start(conn, request).next().await?;
+                    let ConnectedNext::StartRoot(start) = connected.next().await? else {
+                        anyhow::bail!("expected the hash seq");
+                    };
+                    let end = write_ranges(
+                        &db,
+                        start.next(),
+                        BlobId::Root,
+                        next_id,
+                        ChunkRanges::empty(),
+                        progress,
+                    )
+                    .await?;
+                    next_id += 1;
+                    let EndBlobNext::Closing(closing) = end.next() else {
+                        anyhow::bail!("got data we have not requested");
+                    };
+                    add_stats(&mut stats, closing.next().await?);
+                    local_hash_seq(&db, &hash)
+                        .await?
+                        .ok_or_else(|| anyhow::anyhow!("hash seq not stored"))?
+                }
+            };
+            progress
+                .send_async(Ok(DownloadProgress::FoundHashSeq {
+                    hash,
+                    children: children.len() as u64,
+                }))
+                .await?;
+            Some(children)
+        }
+    };
+
+    // only request what is not available locally, and keep track of what is still missing
+    // afterwards, to know which blobs the request completes
+    let mut request = Vec::new();
+    let mut rest = Vec::new();
+    let missing = match &children {
+        None => vec![blob_info(&db, &hash).await?.missing_ranges()],
+        Some(children) => {
+            let mut missing = Vec::with_capacity(children.len());
+            for child in children {
+                missing.push(blob_info(&db, child).await?.missing_ranges());
+            }
+            request.push(ChunkRanges::empty());
+            rest.push(ChunkRanges::empty());
+            missing
+        }
+    };
+    for (index, missing) in missing.into_iter().enumerate() {
+        let wanted = wanted(index);
+        request.push(missing.clone() & wanted.clone());
+        rest.push(missing - wanted);
+    }
+
+    if request.iter().any(|r| !r.is_empty()) {
+        let conn = connect_once(&mut conn, &endpoint, &opts.nodes, progress).await?;
+        let request = GetRequest::new(hash, RangeSpecSeq::from_ranges(request));
+        let connected = fsm::start(conn, request).next().await?;
+        let mut next = match connected.next().await? {
+            ConnectedNext::StartRoot(start) => {
+                let rest = rest[0].clone();
+                let end =
+                    write_ranges(&db, start.next(), BlobId::Root, next_id, rest, progress).await?;
+                next_id += 1;
+                end.next()
+            }
+            ConnectedNext::StartChild(start) => EndBlobNext::MoreChildren(start),
+            ConnectedNext::Closing(closing) => EndBlobNext::Closing(closing),
+        };
+        let closing = loop {
+            let start = match next {
+                EndBlobNext::MoreChildren(start) => start,
+                EndBlobNext::Closing(closing) => break closing,
+            };
+            let offset = start.child_offset();
+            let Some(child) = children
+                .as_ref()
+                .and_then(|children| children.get(offset as usize))
+            else {
+                break start.finish();
+            };
+            let child_id = BlobId::Child(std::num::NonZeroU64::MIN.saturating_add(offset));
+            let rest = rest[offset as usize + 1].clone();
+            let end =
+                write_ranges(&db, start.next(*child), child_id, next_id, rest, progress).await?;
+            next_id += 1;
+            next = end.next();
+        };
+        add_stats(&mut stats, closing.next().await?);
+    }
+
+    match opts.tag {
+        SetTagOption::Named(tag) => db.set_tag(tag, Some(hash_and_format)).await?,
+        SetTagOption::Auto => {
+            db.create_tag(hash_and_format).await?;
+        }
+    }
+    progress
+        .send_async(Ok(DownloadProgress::AllDone(stats)))
+        .await?;
+    Ok(())
+}
+
+/// Read the children of a hash sequence, if it is completely available locally.
+async fn local_hash_seq<D: Store>(
+    db: &D,
+    hash: &iroh::blobs::Hash,
+) -> anyhow::Result<Option<Vec<iroh::blobs::Hash>>> {
+    let Some(entry) = db.get(hash).await? else {
+        return Ok(None);
+    };
+    if !entry.is_complete() {
+        return Ok(None);
+    }
+    let (mut stream, _) = iroh::blobs::hashseq::parse_hash_seq(entry.data_reader().await?).await?;
+    let mut children = Vec::new();
+    while let Some(child) = stream.next().await? {
+        children.push(child);
+    }
+    Ok(Some(children))
+}
+
+/// Connect to a provider, unless already connected.
+async fn connect_once(
+    conn: &mut Option<iroh::net::endpoint::Connection>,
+    endpoint: &iroh::net::Endpoint,
+    nodes: &[iroh::net::NodeAddr],
+    progress: &DownloadProgressSender,
+) -> anyhow::Result<iroh::net::endpoint::Connection> {
+    if let Some(conn) = conn {
+        return Ok(conn.clone());
+    }
+    let new = connect_any(endpoint, nodes).await?;
+    progress
+        .send_async(Ok(iroh::blobs::get::db::DownloadProgress::Connected))
+        .await?;
+    Ok(conn.insert(new).clone())
+}
+
+/// Add the stats of a single request to the stats of the whole download.
+fn add_stats(total: &mut iroh::blobs::get::Stats, stats: iroh::blobs::get::Stats) {
+    total.bytes_written += stats.bytes_written;
+    total.bytes_read += stats.bytes_read;
+    total.elapsed += stats.elapsed;
+}
+
+/// Maps the progress ids of consecutive operations, which each count from 0, to ids that are
+/// unique across all of them.
+#[derive(Debug, Default)]
+pub(crate) struct IdRemap {
+    ids: HashMap<u64, u64>,
+    next_id: u64,
+}
+
+impl IdRemap {
+    /// Start mapping the ids of the next operation.
+    pub(crate) fn reset(&mut self) {
+        self.ids.clear();
+    }
+
+    /// Assign a unique id to a newly found `id`.
+    pub(crate) fn found(&mut self, id: u64) -> u64 {
+        *self.ids.entry(id).or_insert_with(|| {
+            self.next_id += 1;
+            self.next_id - 1
+        })
+    }
+
+    /// The unique id assigned to `id`, or `id` itself if it was never found.
+    pub(crate) fn get(&self, id: u64) -> u64 {
+        self.ids.get(&id).copied().unwrap_or(id)
+    }
+}
+
+/// Connect to the first provider that is reachable.
+async fn connect_any(
+    endpoint: &iroh::net::Endpoint,
+    nodes: &[iroh::net::NodeAddr],
+) -> anyhow::Result<iroh::net::endpoint::Connection> {
+    let mut last_err = anyhow::anyhow!("no provider nodes given");
+    for node in nodes {
+        match endpoint
+            .connect(node.clone(), iroh::blobs::protocol::ALPN)
+            .await
+        {
+            Ok(conn) => return Ok(conn),
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+/// Write the requested ranges of a single blob to the store.
+///
+/// `missing` are the ranges of the blob that are neither available locally nor requested.
+/// If there are none within the size of the blob, it is marked as complete.
+async fn write_ranges<D: Store>(
+    db: &D,
+    header: iroh::blobs::get::fsm::AtBlobHeader,
+    child: iroh::blobs::get::db::BlobId,
+    id: u64,
+    missing: ChunkRanges,
+    progress: &DownloadProgressSender,
+) -> anyhow::Result<iroh::blobs::get::fsm::AtEndBlob> {
+    use iroh::blobs::get::db::DownloadProgress;
+
+    let (content, size) = header.next().await?;
+    let hash = content.hash();
+    let entry = db.get_or_create(hash, size).await?;
+    progress
+        .send_async(Ok(DownloadProgress::Found {
+            id,
+            child,
+            hash,
+            size,
+        }))
+        .await?;
+    let mut writer = ProgressBatchWriter {
+        inner: entry.batch_writer().await?,
+        id,
+        progress: progress.clone(),
+    };
+    let end = content.write_all_batch(&mut writer).await?;
+    writer.sync().await?;
+    drop(writer);
+
+    // like the downloader of the node, this relies on the local data and the request
+    // covering the blob, checking the written data again would be too expensive
+    if (missing & ChunkRanges::from(..ChunkNum::chunks(size))).is_empty() {
+        db.insert_complete(entry).await?;
+    }
+    progress
+        .send_async(Ok(DownloadProgress::Done { id }))
+        .await?;
+    Ok(end)
+}
+
+/// A [`BaoBatchWriter`] that reports a progress event for each batch written.
+struct ProgressBatchWriter<W> {
+    inner: W,
+    id: u64,
+    progress: DownloadProgressSender,
+}
+
+impl<W: BaoBatchWriter> BaoBatchWriter for ProgressBatchWriter<W> {
+    async fn write_batch(&mut self, size: u64, batch: Vec<BaoContentItem>) -> std::io::Result<()> {
+        let offset = batch.iter().find_map(|item| match item {
+            BaoContentItem::Leaf(leaf) => Some(leaf.offset),
+            _ => None,
+        });
+        self.inner.write_batch(size, batch).await?;
+        if let Some(offset) = offset {
+            let progress = iroh::blobs::get::db::DownloadProgress::Progress {
+                id: self.id,
+                offset,
+            };
+            // the receiver is gone if the download was aborted
+            self.progress
+                .send_async(Ok(progress))
+                .await
+                .map_err(|_| std::io::Error::other("download aborted"))?;
+        }
+        Ok(())
+    }
+
+    async fn sync(&mut self) -> std::io::Result<()> {
+        self.inner.sync().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::node::Iroh;
+    use crate::{CallbackError, DocExportProgressType, NodeOptions};
+    use bytes::Bytes;
+    use rand::RngCore;
+    use tokio::io::AsyncWriteExt;
+    use tracing_subscriber::FmtSubscriber;
+
+    #[test]
+    fn test_hash() {
+        let hash_str = "6vp273v6cqbbq7xesa2xfrdt3oajykgeifprn3pj4p6y76654amq";
+        let hex_str = "f55fafeebe1402187ee4903572c473db809c28c4415f16ede9e3fd8ffbdde019";
+        let bytes = b"\xf5\x5f\xaf\xee\xbe\x14\x02\x18\x7e\xe4\x90\x35\x72\xc4\x73\xdb\x80\x9c\x28\xc4\x41\x5f\x16\xed\xe9\xe3\xfd\x8f\xfb\xdd\xe0\x19".to_vec();
+
+        // create hash from string
+        let hash = Hash::from_string(hash_str.into()).unwrap();
+
+        // test methods are as expected
+        assert_eq!(hash_str.to_string(), hash.to_string());
+        assert_eq!(bytes.to_vec(), hash.to_bytes());
+        assert_eq!(hex_str.to_string(), hash.to_hex());
+
+        // create hash from bytes
+        let hash_0 = Hash::from_bytes(bytes.clone()).unwrap();
+
+        // test methods are as expected
+        assert_eq!(hash_str.to_string(), hash_0.to_string());
+        assert_eq!(bytes, hash_0.to_bytes());
+        assert_eq!(hex_str.to_string(), hash_0.to_hex());
+
+        // test that the eq function works
+        assert!(hash.equal(&hash_0));
+        assert!(hash_0.equal(&hash));
+    }
+
+    #[tokio::test]
+    async fn test_blobs_add_get_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = Iroh::persistent(dir.into_path().display().to_string())
+            .await
+            .unwrap();
+
+        let sizes = [1, 10, 100, 1000, 10000, 100000];
+        let mut hashes = Vec::new();
+        for size in sizes.iter() {
+            let hash = blobs_add_get_bytes_size(&node, *size);
+            hashes.push(hash)
+        }
+    }
+
+    async fn blobs_add_get_bytes_size(node: &Iroh, size: usize) -> Arc<Hash> {
+        // create bytes
+        let mut bytes = vec![0; size];
+        rand::thread_rng().fill_bytes(&mut bytes);
         // add blob
         let add_outcome = node.blobs().add_bytes(bytes.to_vec()).await.unwrap();
         // check outcome
@@ -1695,6 +4524,148 @@ mod tests {
         hash
     }
 
+    #[tokio::test]
+    async fn test_blob_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = Iroh::persistent(dir.into_path().display().to_string())
+            .await
+            .unwrap();
+
+        let mut bytes = vec![0; 100_000];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let add_outcome = node.blobs().add_bytes(bytes.clone()).await.unwrap();
+
+        let reader = node.blobs().open_reader(add_outcome.hash).await.unwrap();
+        assert_eq!(reader.size(), bytes.len() as u64);
+        assert!(reader.is_complete());
+
+        // read the whole blob in chunks
+        let mut got = Vec::new();
+        loop {
+            let chunk = reader.read(4096).await.unwrap();
+            if chunk.is_empty() {
+                break;
+            }
+            assert!(chunk.len() <= 4096);
+            got.extend_from_slice(&chunk);
+        }
+        assert_eq!(got, bytes);
+        assert_eq!(reader.position().await, bytes.len() as u64);
+
+        // seek back and continue reading from the new position
+        reader.seek(50_000).await.unwrap();
+        let chunk = reader.read(10).await.unwrap();
+        assert_eq!(chunk, bytes[50_000..50_010]);
+        assert_eq!(reader.position().await, 50_010);
+
+        // random access does not move the reader
+        let chunk = reader.read_at(99_990, 100).await.unwrap();
+        assert_eq!(chunk, bytes[99_990..]);
+        let chunk = reader.read(10).await.unwrap();
+        assert_eq!(chunk, bytes[50_010..50_020]);
+
+        // out of bounds
+        assert!(reader.seek(100_001).await.is_err());
+        assert!(reader.read_at(100_001, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blob_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = Iroh::persistent(dir.into_path().display().to_string())
+            .await
+            .unwrap();
+
+        struct Callback {
+            found: Arc<Mutex<u64>>,
+        }
+
+        #[async_trait::async_trait]
+        impl AddCallback for Callback {
+            async fn progress(&self, progress: Arc<AddProgress>) -> Result<(), CallbackError> {
+                if let AddProgress::Found(ref f) = *progress {
+                    *self.found.lock().unwrap() += f.size;
+                }
+                Ok(())
+            }
+        }
+
+        let found = Arc::new(Mutex::new(0));
+        let cb = Callback {
+            found: found.clone(),
+        };
+        let writer = node
+            .blobs()
+            .add_stream(Arc::new(SetTagOption::auto()), Arc::new(cb))
+            .await
+            .unwrap();
+
+        let mut bytes = vec![0; 100_000];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        for chunk in bytes.chunks(4096) {
+            writer.write(chunk.to_vec()).await.unwrap();
+        }
+        let outcome = writer.finish().await.unwrap();
+        assert_eq!(outcome.size, bytes.len() as u64);
+        assert_eq!(outcome.format, BlobFormat::Raw);
+        assert_eq!(*found.lock().unwrap(), bytes.len() as u64);
+
+        let got = node.blobs().read_to_bytes(outcome.hash).await.unwrap();
+        assert_eq!(got, bytes);
+
+        // the writer cannot be used once finished
+        assert!(writer.write(vec![1, 2, 3]).await.is_err());
+        assert!(writer.finish().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blob_writer_abort() {
+        let node = Iroh::memory().await.unwrap();
+
+        struct Callback;
+
+        #[async_trait::async_trait]
+        impl AddCallback for Callback {
+            async fn progress(&self, _progress: Arc<AddProgress>) -> Result<(), CallbackError> {
+                Ok(())
+            }
+        }
+
+        let mut bytes = vec![0; 100_000];
+        rand::thread_rng().fill_bytes(&mut bytes);
+
+        // explicitly aborted
+        let writer = node
+            .blobs()
+            .add_stream(
+                Arc::new(SetTagOption::named(b"aborted".to_vec())),
+                Arc::new(Callback),
+            )
+            .await
+            .unwrap();
+        writer.write(bytes[..50_000].to_vec()).await.unwrap();
+        writer.abort().await.unwrap();
+        assert!(writer.finish().await.is_err());
+
+        // dropped mid-stream
+        let writer = node
+            .blobs()
+            .add_stream(
+                Arc::new(SetTagOption::named(b"dropped".to_vec())),
+                Arc::new(Callback),
+            )
+            .await
+            .unwrap();
+        writer.write(bytes[..50_000].to_vec()).await.unwrap();
+        drop(writer);
+
+        // the truncated data is not stored as a blob, nor tagged
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(node.blobs().list().await.unwrap().is_empty());
+        assert!(node.blobs().list_incomplete().await.unwrap().is_empty());
+        assert!(node.tags().list().await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn test_blob_read_write_path() {
         let iroh_dir = tempfile::tempdir().unwrap();
@@ -1936,7 +4907,10 @@ mod tests {
         let remove_hash = hashes.pop().unwrap();
         let remove_tag = tags.pop().unwrap();
         // delete the tag for the first blob
-        node.tags().delete(remove_tag).await.unwrap();
+        node.tags()
+            .delete(Arc::new(crate::Tag::from_bytes(remove_tag)))
+            .await
+            .unwrap();
         // wait for GC to clear the blob. windows test runner is slow & needs like 500ms
         tokio::time::sleep(Duration::from_secs(1)).await;
 
@@ -1951,35 +4925,600 @@ mod tests {
         }
     }
 
-    async fn build_iroh_core(
-        path: &std::path::Path,
-    ) -> iroh::node::Node<iroh::blobs::store::fs::Store> {
-        iroh::node::Node::persistent(path)
+    #[tokio::test]
+    async fn test_list_iter() {
+        let iroh_dir = tempfile::tempdir().unwrap();
+        let node = Iroh::persistent(iroh_dir.into_path().display().to_string())
+            .await
+            .unwrap();
+
+        let num_blobs = 10;
+        let mut hashes = vec![];
+        for i in 0..num_blobs {
+            let output = node.blobs().add_bytes(vec![i as u8; 100]).await.unwrap();
+            hashes.push(output.hash);
+        }
+
+        let iter = node.blobs().list_iter().await.unwrap();
+        let mut got_hashes = vec![];
+        loop {
+            let batch = iter.next_batch(3).await.unwrap();
+            if batch.is_empty() {
+                break;
+            }
+            assert!(batch.len() <= 3);
+            got_hashes.extend(batch);
+        }
+        assert_eq!(num_blobs, got_hashes.len());
+        hashes_exist(&hashes, &got_hashes);
+
+        let iter = node.tags().list_iter().await.unwrap();
+        let tags = iter.next_batch(100).await.unwrap();
+        assert_eq!(num_blobs, tags.len());
+        assert!(iter.next_batch(100).await.unwrap().is_empty());
+
+        let iter = node.blobs().list_incomplete_iter().await.unwrap();
+        assert!(iter.next_batch(100).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_info() {
+        let iroh_dir = tempfile::tempdir().unwrap();
+        let node = Iroh::persistent(iroh_dir.into_path().display().to_string())
+            .await
+            .unwrap();
+
+        let bytes = vec![1u8; 100];
+        let auto = node.blobs().add_bytes(bytes.clone()).await.unwrap();
+        let named = node
+            .blobs()
+            .add_bytes_named(bytes, "named".to_string())
+            .await
+            .unwrap();
+        assert!(auto.hash.equal(&named.hash));
+        let other = node.blobs().add_bytes(vec![2u8; 10]).await.unwrap();
+
+        let infos = node.blobs().list_info().await.unwrap();
+        assert_eq!(2, infos.len());
+        let info = infos.iter().find(|i| i.hash.equal(&auto.hash)).unwrap();
+        assert_eq!(100, info.size);
+        let mut tags = info.tags.clone();
+        tags.sort();
+        let mut expected = vec![auto.tag.clone(), b"named".to_vec()];
+        expected.sort();
+        assert_eq!(expected, tags);
+
+        let iter = node.blobs().list_info_iter().await.unwrap();
+        let infos = iter.next_batch(1).await.unwrap();
+        assert_eq!(1, infos.len());
+        let infos = iter.next_batch(1).await.unwrap();
+        assert_eq!(1, infos.len());
+        assert!(iter.next_batch(1).await.unwrap().is_empty());
+
+        let info = node
+            .blobs()
+            .list_info()
             .await
             .unwrap()
-            .spawn()
+            .into_iter()
+            .find(|i| i.hash.equal(&other.hash))
+            .unwrap();
+        assert_eq!(10, info.size);
+        assert_eq!(vec![other.tag.clone()], info.tags);
+
+        // the tags are looked up in the index of the node as the blobs are returned
+        let iter = node.blobs().list_info_iter().await.unwrap();
+        node.tags()
+            .set(
+                Arc::new(crate::Tag::from_string("late".to_string())),
+                other.hash.clone(),
+                BlobFormat::Raw,
+            )
+            .await
+            .unwrap();
+        let info = iter
+            .next_batch(10)
             .await
             .unwrap()
+            .into_iter()
+            .find(|i| i.hash.equal(&other.hash))
+            .unwrap();
+        let mut expected = vec![other.tag, b"late".to_vec()];
+        expected.sort();
+        assert_eq!(expected, info.tags);
     }
 
     #[tokio::test]
-    async fn iroh_core_blobs_add_get_bytes() {
-        let dir = tempfile::tempdir().unwrap();
-        let node = build_iroh_core(dir.path()).await;
-        let sizes = [1, 10, 100, 1000, 10000, 100000];
-        let mut hashes = Vec::new();
-        for size in sizes.iter() {
-            let hash = iroh_core_blobs_add_get_bytes_size(&node, *size);
-            hashes.push(hash)
+    async fn test_delete_blob_tags() {
+        let iroh_dir = tempfile::tempdir().unwrap();
+        let node = Iroh::persistent(iroh_dir.into_path().display().to_string())
+            .await
+            .unwrap();
+
+        let bytes = vec![1u8; 100];
+        let auto = node.blobs().add_bytes(bytes.clone()).await.unwrap();
+        node.blobs()
+            .add_bytes_named(bytes, "named".to_string())
+            .await
+            .unwrap();
+
+        let mut tags = node.blobs().tags_for(auto.hash.clone()).await.unwrap();
+        tags.sort();
+        let mut expected = vec![auto.tag.clone(), b"named".to_vec()];
+        expected.sort();
+        assert_eq!(expected, tags);
+
+        // refuses to delete a tagged blob
+        match node.blobs().delete_blob(auto.hash.clone(), false).await {
+            Err(DeleteBlobError::Tagged { mut tags }) => {
+                tags.sort();
+                assert_eq!(expected, tags);
+            }
+            res => panic!("unexpected result: {res:?}"),
         }
+        assert!(has_blob(&node, &auto.hash).await);
+
+        // forced deletion removes all tags
+        let mut removed = node
+            .blobs()
+            .delete_blob(auto.hash.clone(), true)
+            .await
+            .unwrap();
+        removed.sort();
+        assert_eq!(expected, removed);
+        assert!(node
+            .blobs()
+            .tags_for(auto.hash.clone())
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(!has_blob(&node, &auto.hash).await);
+
+        // untagged blobs are deleted without force
+        let other = node.blobs().add_bytes(vec![2u8; 10]).await.unwrap();
+        node.tags()
+            .delete(Arc::new(crate::Tag::from_bytes(other.tag)))
+            .await
+            .unwrap();
+        let removed = node
+            .blobs()
+            .delete_blob(other.hash.clone(), false)
+            .await
+            .unwrap();
+        assert!(removed.is_empty());
+        assert!(!has_blob(&node, &other.hash).await);
     }
 
-    async fn iroh_core_blobs_add_get_bytes_size(
-        node: &iroh::node::Node<iroh::blobs::store::fs::Store>,
-        size: usize,
-    ) -> iroh::blobs::Hash {
-        let client = node.client();
-        // create bytes
+    #[tokio::test]
+    async fn test_tags_for_index() {
+        let iroh_dir = tempfile::tempdir().unwrap();
+        let path = iroh_dir.path().display().to_string();
+        let node = Iroh::persistent(path.clone()).await.unwrap();
+
+        let a = node.blobs().add_bytes(vec![1u8; 100]).await.unwrap();
+        let b = node
+            .blobs()
+            .add_bytes_named(vec![2u8; 100], "b".to_string())
+            .await
+            .unwrap();
+        let tag = Arc::new(crate::Tag::from_string("moved".to_string()));
+        node.tags()
+            .set(tag.clone(), a.hash.clone(), BlobFormat::Raw)
+            .await
+            .unwrap();
+        assert_eq!(
+            2,
+            node.blobs().tags_for(a.hash.clone()).await.unwrap().len()
+        );
+
+        // overwriting a tag moves it to the new hash
+        node.tags()
+            .set(tag, b.hash.clone(), BlobFormat::Raw)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![a.tag.clone()],
+            node.blobs().tags_for(a.hash.clone()).await.unwrap()
+        );
+        assert_eq!(
+            vec![b"b".to_vec(), b"moved".to_vec()],
+            node.blobs().tags_for(b.hash.clone()).await.unwrap()
+        );
+
+        // deleted tags are removed from the index
+        node.tags()
+            .delete(Arc::new(crate::Tag::from_bytes(a.tag.clone())))
+            .await
+            .unwrap();
+        assert!(node
+            .blobs()
+            .tags_for(a.hash.clone())
+            .await
+            .unwrap()
+            .is_empty());
+
+        // the index is rebuilt from the store when the node is restarted
+        node.node().shutdown(false).await.unwrap();
+        drop(node);
+        // the store is closed in the background once all handles are gone
+        let mut attempts = 0;
+        let node = loop {
+            match Iroh::persistent(path.clone()).await {
+                Ok(node) => break node,
+                Err(_) if attempts < 50 => {
+                    attempts += 1;
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                Err(err) => panic!("failed to restart the node: {err:?}"),
+            }
+        };
+        assert_eq!(
+            vec![b"b".to_vec(), b"moved".to_vec()],
+            node.blobs().tags_for(b.hash.clone()).await.unwrap()
+        );
+        assert!(node
+            .blobs()
+            .tags_for(a.hash.clone())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_range_spec() {
+        assert!(RangeSpec::all().is_all());
+        assert!(RangeSpec::empty().is_empty());
+        assert!(RangeSpec::from_byte_ranges(vec![]).is_empty());
+
+        // byte ranges are widened to whole chunks
+        let bytes = RangeSpec::from_byte_ranges(vec![
+            ByteRange {
+                start: 100,
+                end: 1500,
+            },
+            ByteRange {
+                start: 4096,
+                end: 5000,
+            },
+        ]);
+        let chunks = RangeSpec::from_chunk_ranges(vec![
+            ChunkRange { start: 0, end: 2 },
+            ChunkRange { start: 4, end: 5 },
+        ]);
+        assert_eq!(bytes, chunks);
+        assert!(!bytes.is_all());
+        assert!(!bytes.is_empty());
+    }
+
+    #[test]
+    fn test_download_mode() {
+        let tag = Arc::new(SetTagOption::auto());
+        let opts = BlobDownloadOptions::new(BlobFormat::Raw, vec![], tag.clone()).unwrap();
+        assert_eq!(opts.mode(), DownloadMode::Direct);
+        let opts =
+            BlobDownloadOptions::with_mode(BlobFormat::Raw, vec![], tag, DownloadMode::Queued)
+                .unwrap();
+        assert_eq!(opts.mode(), DownloadMode::Queued);
+    }
+
+    #[tokio::test]
+    async fn test_download_ranges() {
+        let provider = Iroh::memory().await.unwrap();
+        // the memory store does not keep partial blobs
+        let dir = tempfile::tempdir().unwrap();
+        let getter = Iroh::persistent(dir.path().to_string_lossy().into_owned())
+            .await
+            .unwrap();
+
+        let mut bytes = vec![0; 100_000];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let add_outcome = provider.blobs().add_bytes(bytes.clone()).await.unwrap();
+        let provider_addr = Arc::new(provider.net().node_addr().await.unwrap());
+
+        struct Callback;
+
+        #[async_trait::async_trait]
+        impl DownloadCallback for Callback {
+            async fn progress(
+                &self,
+                _progress: Arc<DownloadProgress>,
+            ) -> Result<(), CallbackError> {
+                Ok(())
+            }
+        }
+
+        // fetch only the start of the blob, more than the store keeps in memory
+        let ranges = RangeSpec::from_byte_ranges(vec![ByteRange {
+            start: 0,
+            end: 32 * 1024,
+        }]);
+        let opts = BlobDownloadOptions::with_ranges(
+            BlobFormat::Raw,
+            vec![provider_addr.clone()],
+            Arc::new(SetTagOption::auto()),
+            vec![Arc::new(ranges)],
+        )
+        .unwrap();
+        getter
+            .blobs()
+            .download(add_outcome.hash.clone(), Arc::new(opts), Arc::new(Callback))
+            .await
+            .unwrap();
+
+        let incomplete = getter.blobs().list_incomplete().await.unwrap();
+        assert_eq!(1, incomplete.len());
+        let reader = getter
+            .blobs()
+            .open_reader(add_outcome.hash.clone())
+            .await
+            .unwrap();
+        assert!(!reader.is_complete());
+        assert_eq!(
+            reader.read_at(0, 32 * 1024).await.unwrap(),
+            bytes[..32 * 1024]
+        );
+
+        // resume the rest of the blob
+        let opts = BlobDownloadOptions::new(
+            BlobFormat::Raw,
+            vec![provider_addr],
+            Arc::new(SetTagOption::auto()),
+        )
+        .unwrap();
+        getter
+            .blobs()
+            .download(add_outcome.hash.clone(), Arc::new(opts), Arc::new(Callback))
+            .await
+            .unwrap();
+        let got = getter
+            .blobs()
+            .read_to_bytes(add_outcome.hash)
+            .await
+            .unwrap();
+        assert_eq!(got, bytes);
+    }
+
+    #[tokio::test]
+    async fn test_download_incremental() {
+        let provider = Iroh::memory().await.unwrap();
+        let getter = Iroh::memory().await.unwrap();
+        let provider_addr = Arc::new(provider.net().node_addr().await.unwrap());
+
+        let random = |len: usize| {
+            let mut bytes = vec![0; len];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            bytes
+        };
+        let a = provider.blobs().add_bytes(random(100_000)).await.unwrap();
+        let b = provider.blobs().add_bytes(random(100_000)).await.unwrap();
+        let c = provider.blobs().add_bytes(random(10_000)).await.unwrap();
+        let collection = Collection::new();
+        collection.push("a".to_string(), &a.hash).unwrap();
+        collection.push("b".to_string(), &b.hash).unwrap();
+        let old = provider
+            .blobs()
+            .create_collection(
+                Arc::new(collection),
+                Arc::new(SetTagOption::auto()),
+                Vec::new(),
+            )
+            .await
+            .unwrap();
+        let collection = provider
+            .blobs()
+            .get_collection(old.hash.clone())
+            .await
+            .unwrap();
+        collection.push("c".to_string(), &c.hash).unwrap();
+        let new = provider
+            .blobs()
+            .create_collection(collection, Arc::new(SetTagOption::auto()), Vec::new())
+            .await
+            .unwrap();
+
+        struct Callback(Mutex<Vec<Arc<DownloadProgress>>>);
+
+        #[async_trait::async_trait]
+        impl DownloadCallback for Callback {
+            async fn progress(&self, progress: Arc<DownloadProgress>) -> Result<(), CallbackError> {
+                self.0.lock().unwrap().push(progress);
+                Ok(())
+            }
+        }
+
+        let opts = BlobDownloadOptions::new(
+            BlobFormat::HashSeq,
+            vec![provider_addr.clone()],
+            Arc::new(SetTagOption::auto()),
+        )
+        .unwrap();
+        getter
+            .blobs()
+            .download(
+                old.hash,
+                Arc::new(opts),
+                Arc::new(Callback(Default::default())),
+            )
+            .await
+            .unwrap();
+
+        // only the hash seq, the collection metadata and `c` are transferred
+        let opts = BlobDownloadOptions::incremental(
+            BlobFormat::HashSeq,
+            vec![provider_addr],
+            Arc::new(SetTagOption::auto()),
+        )
+        .unwrap();
+        let cb = Arc::new(Callback(Default::default()));
+        getter
+            .blobs()
+            .download(new.hash.clone(), Arc::new(opts), cb.clone())
+            .await
+            .unwrap();
+        let events = cb.0.lock().unwrap().clone();
+        let found: Vec<_> = events
+            .iter()
+            .filter(|e| e.r#type() == DownloadProgressType::Found)
+            .map(|e| e.as_found().hash)
+            .collect();
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().any(|hash| hash.equal(&c.hash)));
+        assert!(!found
+            .iter()
+            .any(|hash| hash.equal(&a.hash) || hash.equal(&b.hash)));
+        let done = events.last().unwrap().as_all_done();
+        assert!(done.bytes_read < 20_000);
+
+        let collection = getter.blobs().get_collection(new.hash).await.unwrap();
+        assert_eq!(collection.names().unwrap(), ["a", "b", "c"]);
+        let got = getter.blobs().read_to_bytes(c.hash.clone()).await.unwrap();
+        assert_eq!(got.len(), 10_000);
+    }
+
+    #[tokio::test]
+    async fn test_download_handle() {
+        let provider = Iroh::memory().await.unwrap();
+        let getter = Iroh::memory().await.unwrap();
+
+        let mut bytes = vec![0; 1024 * 1024];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let add_outcome = provider.blobs().add_bytes(bytes.clone()).await.unwrap();
+        let provider_addr = Arc::new(provider.net().node_addr().await.unwrap());
+        let opts = Arc::new(
+            BlobDownloadOptions::new(
+                BlobFormat::Raw,
+                vec![provider_addr],
+                Arc::new(SetTagOption::auto()),
+            )
+            .unwrap(),
+        );
+
+        // cancel before the transfer had a chance to finish
+        let handle = getter
+            .blobs()
+            .download_start(add_outcome.hash.clone(), opts.clone(), None)
+            .await
+            .unwrap();
+        handle.cancel();
+        assert!(matches!(
+            handle.await_done().await,
+            Err(DownloadError::Cancelled)
+        ));
+        assert!(!handle.progress_snapshot().finished);
+
+        let handle = getter
+            .blobs()
+            .download_start(add_outcome.hash.clone(), opts, None)
+            .await
+            .unwrap();
+        let done = handle.await_done().await.unwrap();
+        assert!(done.bytes_read > 0);
+        // waiting again returns the same outcome
+        assert_eq!(done, handle.await_done().await.unwrap());
+
+        let snapshot = handle.progress_snapshot();
+        assert!(snapshot.connected);
+        assert!(snapshot.finished);
+        assert_eq!(snapshot.blobs_found, snapshot.blobs_done);
+
+        let got = getter
+            .blobs()
+            .read_to_bytes(add_outcome.hash)
+            .await
+            .unwrap();
+        assert_eq!(got, bytes);
+    }
+
+    #[tokio::test]
+    async fn test_download_add_providers() {
+        let gone = Iroh::memory().await.unwrap();
+        let provider = Iroh::memory().await.unwrap();
+        let getter = Iroh::memory().await.unwrap();
+
+        let mut bytes = vec![0; 100_000];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let add_outcome = provider.blobs().add_bytes(bytes.clone()).await.unwrap();
+        // the first provider does not have the data, and is not reachable anymore
+        let gone_addr = Arc::new(gone.net().node_addr().await.unwrap());
+        gone.node().shutdown(false).await.unwrap();
+
+        let opts = BlobDownloadOptions::with_mode(
+            BlobFormat::Raw,
+            vec![gone_addr],
+            Arc::new(SetTagOption::auto()),
+            DownloadMode::Queued,
+        )
+        .unwrap();
+        let handle = getter
+            .blobs()
+            .download_start(add_outcome.hash.clone(), Arc::new(opts), None)
+            .await
+            .unwrap();
+        let provider_addr = Arc::new(provider.net().node_addr().await.unwrap());
+        // every call sets the same tag
+        handle
+            .add_providers(vec![provider_addr.clone()])
+            .await
+            .unwrap();
+        handle.add_providers(vec![provider_addr]).await.unwrap();
+        handle.await_done().await.unwrap();
+
+        let got = getter
+            .blobs()
+            .read_to_bytes(add_outcome.hash.clone())
+            .await
+            .unwrap();
+        assert_eq!(got, bytes);
+        // the calls did not create tags of their own
+        let tags = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let tags = getter.tags().list().await.unwrap();
+                if tags.len() == 1 {
+                    break tags;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(tags[0].hash.equal(&add_outcome.hash));
+        assert!(String::from_utf8_lossy(&tags[0].name).starts_with("auto-"));
+    }
+
+    async fn has_blob(node: &Iroh, hash: &Hash) -> bool {
+        let hashes = node.blobs().list().await.unwrap();
+        hashes.iter().any(|h| h.equal(hash))
+    }
+
+    async fn build_iroh_core(
+        path: &std::path::Path,
+    ) -> iroh::node::Node<iroh::blobs::store::fs::Store> {
+        iroh::node::Node::persistent(path)
+            .await
+            .unwrap()
+            .spawn()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn iroh_core_blobs_add_get_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = build_iroh_core(dir.path()).await;
+        let sizes = [1, 10, 100, 1000, 10000, 100000];
+        let mut hashes = Vec::new();
+        for size in sizes.iter() {
+            let hash = iroh_core_blobs_add_get_bytes_size(&node, *size);
+            hashes.push(hash)
+        }
+    }
+
+    async fn iroh_core_blobs_add_get_bytes_size(
+        node: &iroh::node::Node<iroh::blobs::store::fs::Store>,
+        size: usize,
+    ) -> iroh::blobs::Hash {
+        let client = node.client();
+        // create bytes
         let mut bytes = vec![0; size];
         rand::thread_rng().fill_bytes(&mut bytes);
         let bytes: Bytes = bytes.into();
@@ -2094,4 +5633,857 @@ mod tests {
 
         tracing::subscriber::set_global_default(subscriber).ok();
     }
+
+    #[tokio::test]
+    async fn test_export_with_progress() {
+        let iroh_dir = tempfile::tempdir().unwrap();
+        let node = Iroh::memory().await.unwrap();
+
+        let collection = Collection::new();
+        for name in ["a", "b"] {
+            let res = node
+                .blobs()
+                .add_bytes(name.as_bytes().to_vec())
+                .await
+                .unwrap();
+            collection.push(name.to_string(), &res.hash).unwrap();
+        }
+        let res = node
+            .blobs()
+            .create_collection(Arc::new(collection), Arc::new(SetTagOption::auto()), vec![])
+            .await
+            .unwrap();
+
+        struct Callback {
+            events: Arc<Mutex<Vec<DocExportProgressType>>>,
+        }
+
+        #[async_trait::async_trait]
+        impl BlobExportCallback for Callback {
+            async fn progress(
+                &self,
+                progress: Arc<DocExportProgress>,
+            ) -> Result<(), CallbackError> {
+                self.events.lock().unwrap().push(progress.r#type());
+                Ok(())
+            }
+        }
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let out = iroh_dir.path().join("out");
+        node.blobs()
+            .export_with_progress(
+                res.hash.clone(),
+                out.display().to_string(),
+                BlobExportFormat::Collection,
+                BlobExportMode::Copy,
+                Arc::new(Callback {
+                    events: events.clone(),
+                }),
+            )
+            .await
+            .unwrap();
+
+        let events = events.lock().unwrap().clone();
+        let count = |ty| events.iter().filter(|e| **e == ty).count();
+        assert_eq!(count(DocExportProgressType::Found), 2);
+        assert_eq!(count(DocExportProgressType::Done), 2);
+        assert_eq!(events.last(), Some(&DocExportProgressType::AllDone));
+        assert_eq!(std::fs::read(out.join("a")).unwrap(), b"a");
+        assert_eq!(std::fs::read(out.join("b")).unwrap(), b"b");
+
+        // an error from the callback aborts the export
+        struct Abort;
+
+        #[async_trait::async_trait]
+        impl BlobExportCallback for Abort {
+            async fn progress(
+                &self,
+                _progress: Arc<DocExportProgress>,
+            ) -> Result<(), CallbackError> {
+                Err(CallbackError::Error)
+            }
+        }
+        let out = iroh_dir.path().join("aborted");
+        let res = node
+            .blobs()
+            .export_with_progress(
+                res.hash,
+                out.display().to_string(),
+                BlobExportFormat::Collection,
+                BlobExportMode::Copy,
+                Arc::new(Abort),
+            )
+            .await;
+        assert!(res.is_err());
+    }
+
+    fn add_directory_options(include: &[&str], exclude: &[&str]) -> AddDirectoryOptions {
+        AddDirectoryOptions {
+            include: include.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+            symlinks: SymlinkPolicy::Skip,
+            include_hidden: false,
+            in_place: false,
+            tag: Arc::new(SetTagOption::auto()),
+        }
+    }
+
+    #[test]
+    fn test_directory_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for name in [
+            "a.txt",
+            "b.bin",
+            "sub/c.txt",
+            "sub/skip/d.txt",
+            ".hidden/e.txt",
+        ] {
+            let path = root.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, name).unwrap();
+        }
+        let names = |options: AddDirectoryOptions| {
+            DirectoryFilter::new(&options)
+                .unwrap()
+                .scan(root)
+                .unwrap()
+                .into_iter()
+                .map(|(name, _, _)| name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(add_directory_options(&[], &[])),
+            ["a.txt", "b.bin", "sub/c.txt", "sub/skip/d.txt"]
+        );
+        assert_eq!(
+            names(add_directory_options(&["**/*.txt"], &["sub/skip"])),
+            ["a.txt", "sub/c.txt"]
+        );
+        assert_eq!(
+            names(add_directory_options(&[], &["sub/*"])),
+            ["a.txt", "b.bin"]
+        );
+        let mut options = add_directory_options(&["*.txt"], &[]);
+        options.include_hidden = true;
+        assert_eq!(names(options), ["a.txt"]);
+        let mut options = add_directory_options(&["**/*.txt"], &[]);
+        options.include_hidden = true;
+        assert_eq!(
+            names(options),
+            [".hidden/e.txt", "a.txt", "sub/c.txt", "sub/skip/d.txt"]
+        );
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("sub"), root.join("link")).unwrap();
+            assert_eq!(
+                names(add_directory_options(&["link/*"], &[])),
+                Vec::<String>::new()
+            );
+            let mut options = add_directory_options(&["link/*"], &[]);
+            options.symlinks = SymlinkPolicy::Follow;
+            assert_eq!(names(options), ["link/c.txt"]);
+            let mut options = add_directory_options(&[], &[]);
+            options.symlinks = SymlinkPolicy::Error;
+            assert!(DirectoryFilter::new(&options).unwrap().scan(root).is_err());
+        }
+
+        let options = add_directory_options(&["["], &[]);
+        assert!(DirectoryFilter::new(&options).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_add_directory() {
+        let node = Iroh::memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.txt", "sub/b.txt", "sub/c.bin"] {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, name).unwrap();
+        }
+
+        struct Callback {
+            done: Arc<Mutex<Vec<String>>>,
+            aborted: Arc<Mutex<Vec<Option<String>>>>,
+        }
+
+        #[async_trait::async_trait]
+        impl AddDirectoryCallback for Callback {
+            async fn progress(
+                &self,
+                progress: Arc<AddDirectoryProgress>,
+            ) -> Result<(), CallbackError> {
+                match *progress {
+                    AddDirectoryProgress::Done(ref d) => {
+                        self.done.lock().unwrap().push(d.path.clone());
+                    }
+                    AddDirectoryProgress::Abort(ref a) => {
+                        self.aborted.lock().unwrap().push(a.path.clone());
+                    }
+                    _ => {}
+                }
+                Ok(())
+            }
+        }
+        let done = Arc::new(Mutex::new(Vec::new()));
+        let aborted = Arc::new(Mutex::new(Vec::new()));
+        let callback = Arc::new(Callback {
+            done: done.clone(),
+            aborted: aborted.clone(),
+        });
+        let outcome = node
+            .blobs()
+            .add_directory(
+                dir.path().display().to_string(),
+                add_directory_options(&["**/*.txt"], &[]),
+                Some(callback.clone()),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(*done.lock().unwrap(), ["a.txt", "sub/b.txt"]);
+        assert_eq!(outcome.files.len(), 2);
+        assert_eq!(outcome.size, ("a.txt".len() + "sub/b.txt".len()) as u64);
+        let names = outcome.collection.names().unwrap();
+        assert_eq!(names, ["a.txt", "sub/b.txt"]);
+
+        let collection = node
+            .blobs()
+            .get_collection(outcome.hash.clone())
+            .await
+            .unwrap();
+        assert_eq!(collection.names().unwrap(), names);
+        let hash = outcome.files["sub/b.txt"].clone();
+        let bytes = node.blobs().read_to_bytes(hash).await.unwrap();
+        assert_eq!(bytes, b"sub/b.txt");
+        assert!(aborted.lock().unwrap().is_empty());
+
+        // a directory that can not be scanned aborts the import
+        let missing = dir.path().join("missing").display().to_string();
+        let res = node
+            .blobs()
+            .add_directory(missing, add_directory_options(&[], &[]), Some(callback))
+            .await;
+        assert!(res.is_err());
+        assert_eq!(*aborted.lock().unwrap(), [None]);
+
+        // a file named like the metadata entry is imported like any other file
+        std::fs::write(dir.path().join(COLLECTION_METADATA_NAME), b"file").unwrap();
+        let options = AddDirectoryOptions {
+            include_hidden: true,
+            ..add_directory_options(&[], &[])
+        };
+        let outcome = node
+            .blobs()
+            .add_directory(dir.path().display().to_string(), options, None)
+            .await
+            .unwrap();
+        let file = outcome.files[COLLECTION_METADATA_NAME].clone();
+        assert!(outcome
+            .collection
+            .contains(COLLECTION_METADATA_NAME.to_string())
+            .unwrap());
+        assert_eq!(outcome.collection.metadata().unwrap().unwrap(), file);
+    }
+
+    #[test]
+    fn test_collection_edit() {
+        let hash = |data: &[u8]| Hash(iroh::blobs::Hash::new(data));
+        let (a, b, c, meta) = (hash(b"a"), hash(b"b"), hash(b"c"), hash(b"meta"));
+        let collection = Collection::new();
+        collection.push("a".to_string(), &a).unwrap();
+        collection.push("c".to_string(), &c).unwrap();
+
+        collection.insert_at(1, "b".to_string(), &b).unwrap();
+        assert!(collection.insert_at(4, "d".to_string(), &b).is_err());
+        assert_eq!(collection.names().unwrap(), ["a", "b", "c"]);
+        assert_eq!(*collection.get("b".to_string()).unwrap().unwrap(), b);
+        assert!(collection.get("d".to_string()).unwrap().is_none());
+        assert!(collection.contains("c".to_string()).unwrap());
+
+        collection.rename("b".to_string(), "x".to_string()).unwrap();
+        assert!(collection
+            .rename("missing".to_string(), "y".to_string())
+            .is_err());
+        assert!(collection.rename("a".to_string(), "c".to_string()).is_err());
+        collection.rename("a".to_string(), "a".to_string()).unwrap();
+        assert_eq!(collection.names().unwrap(), ["a", "x", "c"]);
+
+        // a trailing `/` only marks nested collections for the tree APIs
+        collection.push_collection("d".to_string(), &b).unwrap();
+        assert_eq!(collection.names().unwrap(), ["a", "x", "c", "d/"]);
+        collection
+            .rename("d/".to_string(), "e/".to_string())
+            .unwrap();
+        assert_eq!(collection.collections().unwrap()[0].name, "e");
+        assert_eq!(*collection.remove("e/".to_string()).unwrap().unwrap(), b);
+        collection.push("d/".to_string(), &b).unwrap();
+        assert_eq!(*collection.remove("d/".to_string()).unwrap().unwrap(), b);
+
+        assert_eq!(*collection.remove("x".to_string()).unwrap().unwrap(), b);
+        assert!(collection.remove("x".to_string()).unwrap().is_none());
+        assert!(!collection.contains("x".to_string()).unwrap());
+
+        assert!(collection.metadata().unwrap().is_none());
+        collection
+            .set_metadata(Some(Arc::new(meta.clone())))
+            .unwrap();
+        assert_eq!(*collection.metadata().unwrap().unwrap(), meta);
+        // the metadata entry is a regular entry, stored first
+        assert_eq!(
+            collection.names().unwrap(),
+            [COLLECTION_METADATA_NAME, "a", "c"]
+        );
+        assert_eq!(
+            *collection
+                .get(COLLECTION_METADATA_NAME.to_string())
+                .unwrap()
+                .unwrap(),
+            meta
+        );
+        collection.set_metadata(Some(Arc::new(a.clone()))).unwrap();
+        assert_eq!(collection.len().unwrap(), 3);
+        assert_eq!(*collection.metadata().unwrap().unwrap(), a);
+        collection.set_metadata(None).unwrap();
+        assert!(collection.metadata().unwrap().is_none());
+        assert_eq!(collection.names().unwrap(), ["a", "c"]);
+        collection
+            .push(COLLECTION_METADATA_NAME.to_string(), &meta)
+            .unwrap();
+        assert_eq!(*collection.metadata().unwrap().unwrap(), meta);
+    }
+
+    #[test]
+    fn test_collection_diff() {
+        let hash = |data: &[u8]| iroh::blobs::Hash::new(data);
+        let old: iroh::blobs::format::collection::Collection = [
+            (COLLECTION_METADATA_NAME.to_string(), hash(b"meta v1")),
+            ("same".to_string(), hash(b"same")),
+            ("changed".to_string(), hash(b"v1")),
+            ("removed".to_string(), hash(b"removed")),
+        ]
+        .into_iter()
+        .collect();
+        let new: iroh::blobs::format::collection::Collection = [
+            (COLLECTION_METADATA_NAME.to_string(), hash(b"meta v2")),
+            ("added".to_string(), hash(b"added")),
+            ("changed".to_string(), hash(b"v2")),
+            ("same".to_string(), hash(b"same")),
+        ]
+        .into_iter()
+        .collect();
+
+        let diff = CollectionDiff::new(&old, &new);
+        let names =
+            |links: &[LinkAndName]| links.iter().map(|l| l.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&diff.added), ["added"]);
+        assert_eq!(names(&diff.removed), ["removed"]);
+        assert_eq!(diff.changed.len(), 2);
+        assert_eq!(diff.changed[0].name, COLLECTION_METADATA_NAME);
+        assert_eq!(diff.changed[1].name, "changed");
+        assert_eq!(diff.changed[1].old.0, hash(b"v1"));
+        assert_eq!(diff.changed[1].new.0, hash(b"v2"));
+
+        let diff = CollectionDiff::new(&old, &old);
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_nested_collections() {
+        let node = Iroh::memory().await.unwrap();
+        let blobs = node.blobs();
+        let add = |data: &'static [u8]| {
+            let blobs = node.blobs();
+            async move { blobs.add_bytes(data.to_vec()).await.unwrap().hash }
+        };
+
+        let sub = Collection::new();
+        sub.push("c.txt".to_string(), &*add(b"c").await).unwrap();
+        let sub = blobs
+            .create_collection(Arc::new(sub), Arc::new(SetTagOption::auto()), vec![])
+            .await
+            .unwrap();
+        let dir = Collection::new();
+        dir.push("b.txt".to_string(), &*add(b"b").await).unwrap();
+        dir.push_collection("sub".to_string(), &sub.hash).unwrap();
+        let dir = blobs
+            .create_collection(Arc::new(dir), Arc::new(SetTagOption::auto()), vec![])
+            .await
+            .unwrap();
+        let root = Collection::new();
+        root.push_collection("dir/".to_string(), &dir.hash).unwrap();
+        root.push("a.txt".to_string(), &*add(b"a").await).unwrap();
+        assert_eq!(root.names().unwrap(), ["dir/", "a.txt"]);
+        let collections = root.collections().unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].name, "dir");
+        let root = blobs
+            .create_collection(Arc::new(root), Arc::new(SetTagOption::auto()), vec![])
+            .await
+            .unwrap();
+
+        let tree = blobs
+            .get_collection_recursive(root.hash.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            tree.names().unwrap(),
+            ["a.txt", "dir/b.txt", "dir/sub/c.txt"]
+        );
+
+        let out = tempfile::tempdir().unwrap();
+        blobs
+            .export_tree(
+                root.hash,
+                out.path().display().to_string(),
+                BlobExportMode::Copy,
+                None,
+            )
+            .await
+            .unwrap();
+        for (path, content) in [("a.txt", "a"), ("dir/b.txt", "b"), ("dir/sub/c.txt", "c")] {
+            let got = std::fs::read_to_string(out.path().join(path)).unwrap();
+            assert_eq!(got, content);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collection_tree_limits() {
+        let node = Iroh::memory().await.unwrap();
+        let blobs = node.blobs();
+        // every level holds the next one twice, so its paths double with each level
+        let nest = |levels: usize| {
+            let blobs = node.blobs();
+            async move {
+                let mut hash = blobs.add_bytes(b"leaf".to_vec()).await.unwrap().hash;
+                let mut format = BlobFormat::Raw;
+                for _ in 0..levels {
+                    let level = Collection::new();
+                    for name in ["a", "b"] {
+                        match format {
+                            BlobFormat::Raw => level.push(name.to_string(), &hash).unwrap(),
+                            BlobFormat::HashSeq => {
+                                level.push_collection(name.to_string(), &hash).unwrap()
+                            }
+                        }
+                    }
+                    let tag = Arc::new(SetTagOption::auto());
+                    let res = blobs.create_collection(Arc::new(level), tag, vec![]);
+                    hash = res.await.unwrap().hash;
+                    format = BlobFormat::HashSeq;
+                }
+                hash
+            }
+        };
+
+        let small = nest(3).await;
+        let tree = blobs.get_collection_recursive(small).await.unwrap();
+        assert_eq!(tree.len().unwrap(), 8);
+
+        let wide = nest(20).await;
+        let err = blobs.get_collection_recursive(wide).await.unwrap_err();
+        assert!(err.to_string().contains("entries"), "{err}");
+
+        let deep = nest(MAX_TREE_DEPTH + 2).await;
+        let err = blobs.get_collection_recursive(deep).await.unwrap_err();
+        assert!(err.to_string().contains("levels deep"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_download_recursive() {
+        let provider = Iroh::memory().await.unwrap();
+        let getter = Iroh::memory().await.unwrap();
+        let provider_addr = Arc::new(provider.net().node_addr().await.unwrap());
+        let add = |data: &'static [u8]| {
+            let blobs = provider.blobs();
+            async move { blobs.add_bytes(data.to_vec()).await.unwrap().hash }
+        };
+        let create = |collection: Collection| {
+            let blobs = provider.blobs();
+            async move {
+                let tag = Arc::new(SetTagOption::auto());
+                let res = blobs.create_collection(Arc::new(collection), tag, vec![]);
+                res.await.unwrap().hash
+            }
+        };
+
+        let sub = Collection::new();
+        sub.push("c.txt".to_string(), &*add(b"c").await).unwrap();
+        let sub = create(sub).await;
+        let dir = Collection::new();
+        dir.push("b.txt".to_string(), &*add(b"b").await).unwrap();
+        dir.push_collection("sub".to_string(), &sub).unwrap();
+        let dir = create(dir).await;
+        let root = Collection::new();
+        root.push("a.txt".to_string(), &*add(b"a").await).unwrap();
+        root.push_collection("dir".to_string(), &dir).unwrap();
+        let root = create(root).await;
+
+        struct Callback(Mutex<Vec<Arc<DownloadProgress>>>);
+
+        #[async_trait::async_trait]
+        impl DownloadCallback for Callback {
+            async fn progress(&self, progress: Arc<DownloadProgress>) -> Result<(), CallbackError> {
+                self.0.lock().unwrap().push(progress);
+                Ok(())
+            }
+        }
+        let download = |tag: SetTagOption| {
+            let blobs = getter.blobs();
+            let (root, provider_addr) = (root.clone(), provider_addr.clone());
+            async move {
+                let opts = BlobDownloadOptions::new(
+                    BlobFormat::HashSeq,
+                    vec![provider_addr],
+                    Arc::new(tag),
+                )
+                .unwrap();
+                let cb = Arc::new(Callback(Default::default()));
+                blobs
+                    .download_recursive(root, Arc::new(opts), cb.clone())
+                    .await
+                    .unwrap();
+                let events = cb.0.lock().unwrap().clone();
+                events
+            }
+        };
+
+        let events = download(SetTagOption::named(b"tree".to_vec())).await;
+        // every download has a unique id, and there is a single `AllDone` at the end
+        let ids: Vec<_> = events
+            .iter()
+            .filter(|e| e.r#type() == DownloadProgressType::Found)
+            .map(|e| e.as_found().id)
+            .collect();
+        let unique: std::collections::BTreeSet<_> = ids.iter().collect();
+        assert_eq!(ids.len(), unique.len());
+        let done = events
+            .iter()
+            .filter(|e| e.r#type() == DownloadProgressType::AllDone)
+            .count();
+        assert_eq!(done, 1);
+        assert_eq!(
+            events.last().unwrap().r#type(),
+            DownloadProgressType::AllDone
+        );
+
+        let tree = getter
+            .blobs()
+            .get_collection_recursive(root.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            tree.names().unwrap(),
+            ["a.txt", "dir/b.txt", "dir/sub/c.txt"]
+        );
+        let tags = |prefix: &'static str| {
+            let tags = getter.tags();
+            async move {
+                let mut names: Vec<_> = tags
+                    .list()
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|tag| String::from_utf8(tag.name).unwrap())
+                    .filter(|name| name.starts_with(prefix))
+                    .collect();
+                names.sort();
+                names
+            }
+        };
+        assert_eq!(tags("tree").await, ["tree", "tree/dir", "tree/dir/sub"]);
+
+        // nested collections are tagged by the root hash, downloading again replaces them
+        download(SetTagOption::auto()).await;
+        download(SetTagOption::auto()).await;
+        let by_root = format!("{root}/");
+        let derived = tags("")
+            .await
+            .into_iter()
+            .filter(|name| name.starts_with(&by_root))
+            .collect::<Vec<_>>();
+        assert_eq!(derived, [format!("{root}/dir"), format!("{root}/dir/sub")]);
+        assert_eq!(getter.tags().list().await.unwrap().len(), 3 + 2 + 2);
+    }
+
+    #[tokio::test]
+    async fn test_blob_lease() {
+        let node = Iroh::memory().await.unwrap();
+        let add = || async {
+            let res = node.blobs().add_bytes(b"lease".to_vec()).await.unwrap();
+            node.tags()
+                .delete(Arc::new(crate::Tag::from_bytes(res.tag)))
+                .await
+                .unwrap();
+            res.hash
+        };
+        let has = |hash: Arc<Hash>| {
+            let blobs = node.blobs();
+            async move { blobs.list().await.unwrap().contains(&hash) }
+        };
+        let hash = add().await;
+
+        let lease = node
+            .blobs()
+            .protect(hash.clone(), BlobFormat::Raw, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(lease.is_active());
+        lease.renew(Duration::from_secs(120)).unwrap();
+        // the untagged blob survives garbage collection while the lease is held
+        node.node().run_gc().await.unwrap();
+        assert!(has(hash.clone()).await);
+
+        lease.release();
+        assert!(!lease.is_active());
+        assert!(lease.renew(Duration::from_secs(60)).is_err());
+        node.node().run_gc().await.unwrap();
+        assert!(!has(hash.clone()).await);
+
+        // shortening the lease makes it expire early
+        let hash = add().await;
+        let lease = node
+            .blobs()
+            .protect(hash.clone(), BlobFormat::Raw, Duration::from_secs(60))
+            .await
+            .unwrap();
+        lease.renew(Duration::from_millis(10)).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!lease.is_active());
+
+        // dropping the lease releases it
+        let hash = add().await;
+        let lease = node
+            .blobs()
+            .protect(hash.clone(), BlobFormat::Raw, Duration::from_secs(60))
+            .await
+            .unwrap();
+        drop(lease);
+        node.node().run_gc().await.unwrap();
+        assert!(!has(hash).await);
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let node = Iroh::memory().await.unwrap();
+        let res = node.blobs().add_bytes(b"hello".to_vec()).await.unwrap();
+        // a tag for a blob that is not in the store
+        let missing = Arc::new(Hash::new(b"missing".to_vec()));
+        node.tags()
+            .set(
+                Arc::new(crate::Tag::from_string("missing".to_string())),
+                missing.clone(),
+                BlobFormat::Raw,
+            )
+            .await
+            .unwrap();
+
+        struct Callback {
+            events: Arc<Mutex<Vec<Arc<BlobValidateProgress>>>>,
+        }
+
+        #[async_trait::async_trait]
+        impl BlobValidateCallback for Callback {
+            async fn progress(
+                &self,
+                progress: Arc<BlobValidateProgress>,
+            ) -> Result<(), CallbackError> {
+                self.events.lock().unwrap().push(progress);
+                Ok(())
+            }
+        }
+        let events = Arc::new(Mutex::new(Vec::new()));
+        node.blobs()
+            .validate(
+                true,
+                Arc::new(Callback {
+                    events: events.clone(),
+                }),
+            )
+            .await
+            .unwrap();
+
+        let events = events.lock().unwrap().clone();
+        let status = |hash: &Hash| {
+            events
+                .iter()
+                .filter(|e| e.r#type() == BlobValidateProgressType::Entry)
+                .map(|e| e.as_entry())
+                .find(|e| e.hash.equal(hash))
+                .map(|e| e.status)
+        };
+        assert_eq!(status(&res.hash), Some(BlobValidateStatus::Complete));
+        assert_eq!(status(&missing), Some(BlobValidateStatus::Missing));
+        let done = events.last().unwrap().as_all_done();
+        assert_eq!(done.corrupt, 0);
+        assert_eq!(done.missing, 1);
+        assert_eq!(done.dropped, 0);
+    }
+
+    #[tokio::test]
+    async fn test_validate_client() {
+        let opts = NodeOptions {
+            enable_rpc: true,
+            ..Default::default()
+        };
+        let node = Iroh::memory_with_options(opts).await.unwrap();
+        let client = Iroh::client(Some(node.node().my_rpc_addr().unwrap()))
+            .await
+            .unwrap();
+
+        struct Callback(Arc<Mutex<u64>>);
+
+        #[async_trait::async_trait]
+        impl BlobValidateCallback for Callback {
+            async fn progress(
+                &self,
+                _progress: Arc<BlobValidateProgress>,
+            ) -> Result<(), CallbackError> {
+                *self.0.lock().unwrap() += 1;
+                Ok(())
+            }
+        }
+        let events = Arc::new(Mutex::new(0));
+        let res = client
+            .blobs()
+            .validate(true, Arc::new(Callback(events.clone())))
+            .await;
+        assert!(res.is_err());
+        // rejected before checking, let alone repairing, the store of the node
+        assert_eq!(*events.lock().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_validate_corrupt() {
+        use std::io::{Seek, Write};
+
+        let provider = Iroh::memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let node = Iroh::persistent(dir.path().to_string_lossy().into_owned())
+            .await
+            .unwrap();
+
+        let mut bytes = vec![0; 100_000];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let complete = node.blobs().add_bytes(bytes.clone()).await.unwrap();
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let partial = provider.blobs().add_bytes(bytes).await.unwrap();
+
+        struct DownloadCb;
+
+        #[async_trait::async_trait]
+        impl DownloadCallback for DownloadCb {
+            async fn progress(
+                &self,
+                _progress: Arc<DownloadProgress>,
+            ) -> Result<(), CallbackError> {
+                Ok(())
+            }
+        }
+        let ranges = RangeSpec::from_byte_ranges(vec![ByteRange {
+            start: 0,
+            end: 64 * 1024,
+        }]);
+        let opts = BlobDownloadOptions::with_ranges(
+            BlobFormat::Raw,
+            vec![Arc::new(provider.net().node_addr().await.unwrap())],
+            Arc::new(SetTagOption::auto()),
+            vec![Arc::new(ranges)],
+        )
+        .unwrap();
+        node.blobs()
+            .download(partial.hash.clone(), Arc::new(opts), Arc::new(DownloadCb))
+            .await
+            .unwrap();
+
+        struct Callback {
+            events: Arc<Mutex<Vec<Arc<BlobValidateProgress>>>>,
+        }
+
+        #[async_trait::async_trait]
+        impl BlobValidateCallback for Callback {
+            async fn progress(
+                &self,
+                progress: Arc<BlobValidateProgress>,
+            ) -> Result<(), CallbackError> {
+                self.events.lock().unwrap().push(progress);
+                Ok(())
+            }
+        }
+        async fn validate(node: &Iroh, repair: bool) -> Vec<Arc<BlobValidateProgress>> {
+            let events = Arc::new(Mutex::new(Vec::new()));
+            node.blobs()
+                .validate(
+                    repair,
+                    Arc::new(Callback {
+                        events: events.clone(),
+                    }),
+                )
+                .await
+                .unwrap();
+            let events = events.lock().unwrap().clone();
+            events
+        }
+
+        let done = validate(&node, false).await.last().unwrap().as_all_done();
+        assert_eq!((done.complete, done.partial, done.corrupt), (1, 1, 0));
+
+        // overwrite some of the data of both blobs
+        for hash in [&complete.hash, &partial.hash] {
+            let path = dir
+                .path()
+                .join("blobs/data")
+                .join(format!("{}.data", hash.to_hex()));
+            let mut file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+            file.seek(std::io::SeekFrom::Start(20_000)).unwrap();
+            file.write_all(&[0; 1024]).unwrap();
+        }
+
+        let events = validate(&node, true).await;
+        let entry = |hash: &Hash| {
+            events
+                .iter()
+                .filter(|e| e.r#type() == BlobValidateProgressType::Entry)
+                .map(|e| e.as_entry())
+                .find(|e| e.hash.equal(hash) && e.status != BlobValidateStatus::Missing)
+                .unwrap()
+        };
+        for hash in [&complete.hash, &partial.hash] {
+            let entry = entry(hash);
+            assert_eq!(entry.status, BlobValidateStatus::Corrupt);
+            assert!(entry.dropped);
+        }
+        let done = events.last().unwrap().as_all_done();
+        assert_eq!(done.corrupt, 2);
+        assert_eq!(done.dropped, 2);
+        assert!(node.blobs().list().await.unwrap().is_empty());
+        assert!(node.blobs().list_incomplete().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_store_stats() {
+        let iroh_dir = tempfile::tempdir().unwrap();
+        let node = Iroh::persistent(iroh_dir.path().display().to_string())
+            .await
+            .unwrap();
+        let small = node.blobs().add_bytes(vec![1; 100]).await.unwrap();
+        let large = node.blobs().add_bytes(vec![2; 100_000]).await.unwrap();
+
+        let stats = node.blobs().store_stats().await.unwrap();
+        assert_eq!(stats.complete_count, 2);
+        assert_eq!(stats.complete_bytes, small.size + large.size);
+        assert_eq!(stats.partial_count, 0);
+        assert_eq!(stats.inline_count, 1);
+        assert_eq!(stats.inline_bytes, small.size);
+        assert_eq!(stats.owned_file_count, 1);
+        assert_eq!(stats.owned_file_bytes, large.size);
+        assert_eq!(stats.reference_count, 0);
+        assert_eq!(stats.outboard_count, 1);
+        // the outboard of a 100 KB blob is small enough to be inlined
+        assert_eq!(stats.inline_outboard_count, 1);
+        assert_eq!(stats.inline_outboard_bytes, 384);
+        assert_eq!(stats.outboard_bytes, 384);
+    }
 }
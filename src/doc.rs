@@ -1,11 +1,14 @@
 use std::{path::PathBuf, str::FromStr, sync::Arc, time::SystemTime};
 
 use bytes::Bytes;
-use futures::{StreamExt, TryStreamExt};
+use futures::{stream::BoxStream, Stream, StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    ticket::AddrInfoOptions, AuthorId, CallbackError, DocTicket, Hash, Iroh, IrohError, PublicKey,
+    blob::{BatchStream, BlobExportMode, DirectoryFilter, IdRemap},
+    ticket::AddrInfoOptions,
+    AuthorId, CallbackError, DocTicket, Hash, Iroh, IrohError, PublicKey,
 };
 
 #[derive(Debug, uniffi::Enum)]
@@ -52,45 +55,38 @@ impl Docs {
     pub async fn create(&self) -> Result<Arc<Doc>, IrohError> {
         let doc = self.client().docs().create().await?;
 
-        Ok(Arc::new(Doc { inner: doc }))
+        Ok(Arc::new(Doc::new(doc)))
     }
 
     /// Join and sync with an already existing document.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn join(&self, ticket: &DocTicket) -> Result<Arc<Doc>, IrohError> {
         let doc = self.client().docs().import(ticket.clone().into()).await?;
-        Ok(Arc::new(Doc { inner: doc }))
+        Ok(Arc::new(Doc::new(doc)))
     }
 
     /// Join and sync with an already existing document and subscribe to events on that document.
+    ///
+    /// The subscription is bound to the returned `Doc`, see [`Doc::subscribe`].
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn join_and_subscribe(
         &self,
         ticket: &DocTicket,
         cb: Arc<dyn SubscribeCallback>,
-    ) -> Result<Arc<Doc>, IrohError> {
-        let (doc, mut stream) = self
+    ) -> Result<DocAndSubscription, IrohError> {
+        let (doc, stream) = self
             .client()
             .docs()
             .import_and_subscribe(ticket.clone().into())
             .await?;
 
-        tokio::spawn(async move {
-            while let Some(event) = stream.next().await {
-                match event {
-                    Ok(event) => {
-                        if let Err(err) = cb.event(Arc::new(event.into())).await {
-                            println!("cb error: {:?}", err);
-                        }
-                    }
-                    Err(err) => {
-                        println!("rpc error: {:?}", err);
-                    }
-                }
-            }
-        });
+        let doc = Doc::new(doc);
+        let subscription = doc.spawn_subscription(stream.boxed(), cb);
 
-        Ok(Arc::new(Doc { inner: doc }))
+        Ok(DocAndSubscription {
+            doc: Arc::new(doc),
+            subscription: Arc::new(subscription),
+        })
     }
 
     /// List all the docs we have access to on this node.
@@ -119,7 +115,7 @@ impl Docs {
         let namespace_id = iroh::docs::NamespaceId::from_str(&id)?;
         let doc = self.client().docs().open(namespace_id).await?;
 
-        Ok(doc.map(|d| Arc::new(Doc { inner: d })))
+        Ok(doc.map(|d| Arc::new(Doc::new(d))))
     }
 
     /// Delete a document from the local node.
@@ -151,6 +147,57 @@ pub struct NamespaceAndCapability {
 #[derive(Clone, uniffi::Object)]
 pub struct Doc {
     pub(crate) inner: iroh::client::Doc,
+    /// Cancelled when the document is closed, stopping all of its subscriptions.
+    closed: CancellationToken,
+}
+
+impl Doc {
+    pub(crate) fn new(inner: iroh::client::Doc) -> Self {
+        Doc {
+            inner,
+            closed: CancellationToken::new(),
+        }
+    }
+
+    /// Pass the events of `stream` to `cb`, until the subscription is cancelled, the document
+    /// is closed or the stream ends.
+    fn spawn_subscription(
+        &self,
+        mut stream: BoxStream<'static, anyhow::Result<iroh::client::docs::LiveEvent>>,
+        cb: Arc<dyn SubscribeCallback>,
+    ) -> DocSubscription {
+        let cancel = self.closed.child_token();
+        let done = CancellationToken::new();
+        let subscription = DocSubscription {
+            cancel: cancel.clone(),
+            done: done.clone(),
+        };
+        tokio::task::spawn(async move {
+            let _done = done.drop_guard();
+            loop {
+                let event = tokio::select! {
+                    biased;
+
+                    _ = cancel.cancelled() => break,
+                    event = stream.next() => match event {
+                        Some(event) => event,
+                        None => break,
+                    },
+                };
+                let (event, failed) = match event {
+                    Ok(event) => (event.into(), false),
+                    Err(err) => (LiveEvent::Error(format!("{err:#}")), true),
+                };
+                if let Err(err) = cb.event(Arc::new(event)).await {
+                    tracing::warn!("cb error, doc subscription: {:?}", err);
+                }
+                if failed {
+                    break;
+                }
+            }
+        });
+        subscription
+    }
 }
 
 #[uniffi::export]
@@ -162,8 +209,12 @@ impl Doc {
     }
 
     /// Close the document.
+    ///
+    /// Stops the subscriptions created through this `Doc`. Subscriptions created through
+    /// another `Doc` of the same document, e.g. from `docs.open`, keep running.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn close_me(&self) -> Result<(), IrohError> {
+        self.closed.cancel();
         self.inner.close().await.map_err(IrohError::from)
     }
 
@@ -216,22 +267,132 @@ impl Doc {
         Ok(())
     }
 
+    /// Import all files below the directory `root`, one entry per file.
+    ///
+    /// Keys are derived from the file paths with [`crate::path_to_key`], relative to `root` and
+    /// starting with `prefix` as given, so [`Doc::export_prefix`] with the same `prefix`
+    /// exports the files again. Hidden files are imported, symbolic links are skipped.
+    ///
+    /// Keeping the document in sync with the directory requires a `prefix`: with one, file
+    /// keys by `author` starting with it whose file no longer exists are deleted, so
+    /// importing the same directory again brings the document up to date. Use a `prefix`
+    /// that is only used for this directory, e.g. `photos/` rather than `photos`, which also
+    /// matches the keys of `photos2/`. Without a `prefix` the keys are relative to `root`
+    /// and can not be told apart from other keys, so nothing is deleted, and files removed
+    /// from the directory keep their entries.
+    ///
+    /// If `in_place` is true, Iroh will assume that the files will not change and will share
+    /// them in place without copying to the Iroh data directory.
+    ///
+    /// `cb` receives the progress of all files, with ids unique within this import, and a
+    /// single `AllDone` with the key `prefix` at the end.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn import_directory(
+        &self,
+        author: Arc<AuthorId>,
+        root: String,
+        prefix: Option<String>,
+        in_place: bool,
+        cb: Option<Arc<dyn DocImportFileCallback>>,
+    ) -> Result<DocImportDirectoryOutcome, IrohError> {
+        let prefix = prefix.filter(|prefix| !prefix.is_empty());
+        let (root, files) = tokio::task::spawn_blocking(move || {
+            let root = std::fs::canonicalize(root)?;
+            let files = DirectoryFilter::all_files().scan(&root)?;
+            anyhow::Ok((root, files))
+        })
+        .await
+        .map_err(anyhow::Error::from)??;
+
+        let mut keys = std::collections::HashSet::new();
+        let mut ids = IdRemap::default();
+        for (_, path, _) in files {
+            let key = iroh::util::fs::path_to_key(&path, prefix.clone(), Some(root.clone()))?;
+            let mut stream = self
+                .inner
+                .import_file(author.0, key.clone(), path, in_place)
+                .await?;
+            ids.reset();
+            while let Some(progress) = stream.next().await {
+                let mut progress: DocImportProgress = progress?.into();
+                match progress {
+                    DocImportProgress::Found(DocImportProgressFound { ref mut id, .. }) => {
+                        *id = ids.found(*id);
+                    }
+                    DocImportProgress::Progress(DocImportProgressProgress {
+                        ref mut id, ..
+                    })
+                    | DocImportProgress::IngestDone(DocImportProgressIngestDone {
+                        ref mut id,
+                        ..
+                    }) => {
+                        *id = ids.get(*id);
+                    }
+                    DocImportProgress::AllDone(_) => continue,
+                    DocImportProgress::Abort(ref abort) => {
+                        let err = anyhow::anyhow!("import aborted: {}", abort.error);
+                        if let Some(ref cb) = cb {
+                            cb.progress(Arc::new(progress)).await?;
+                        }
+                        return Err(err.into());
+                    }
+                }
+                if let Some(ref cb) = cb {
+                    cb.progress(Arc::new(progress)).await?;
+                }
+            }
+            keys.insert(key);
+        }
+
+        // delete the keys of files that are gone, all of them end with a null byte
+        let prefix = prefix.unwrap_or_default().into_bytes();
+        let mut stale = Vec::new();
+        if !prefix.is_empty() {
+            let query = iroh::docs::store::Query::author(author.0)
+                .key_prefix(&prefix)
+                .build();
+            stale = self
+                .inner
+                .get_many(query)
+                .await?
+                .map_ok(|entry| Bytes::copy_from_slice(entry.key()))
+                .try_filter(|key| {
+                    futures::future::ready(key.ends_with(b"\0") && !keys.contains(key))
+                })
+                .try_collect()
+                .await?;
+        }
+        // `del` removes every key starting with the given one, file keys can only be the
+        // prefix of themselves because they end with the null byte
+        for key in &stale {
+            self.inner.del(author.0, key.clone()).await?;
+        }
+
+        if let Some(ref cb) = cb {
+            let done = DocImportProgress::AllDone(DocImportProgressAllDone { key: prefix });
+            cb.progress(Arc::new(done)).await?;
+        }
+        Ok(DocImportDirectoryOutcome {
+            imported: keys.len() as u64,
+            deleted: stale.len() as u64,
+        })
+    }
+
     /// Export an entry as a file to a given absolute path
+    ///
+    /// With [`BlobExportMode::TryReference`] the node keeps using the exported file as the
+    /// storage of the content, instead of keeping a second copy.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn export_file(
         &self,
         entry: Arc<Entry>,
         path: String,
+        mode: BlobExportMode,
         cb: Option<Arc<dyn DocExportFileCallback>>,
     ) -> Result<(), IrohError> {
         let mut stream = self
             .inner
-            .export_file(
-                entry.0.clone(),
-                std::path::PathBuf::from(path),
-                // TODO(b5) - plumb up the export mode, currently it's always copy
-                iroh::blobs::store::ExportMode::Copy,
-            )
+            .export_file(entry.0.clone(), std::path::PathBuf::from(path), mode.into())
             .await?;
         while let Some(progress) = stream.next().await {
             let progress = progress?;
@@ -242,6 +403,89 @@ impl Doc {
         Ok(())
     }
 
+    /// Export the latest entry of every key starting with `prefix` as a file below `root_dir`.
+    ///
+    /// The path of each file is derived from its key with [`crate::key_to_path`], removing
+    /// `prefix`, so this is the inverse of importing files with keys from [`crate::path_to_key`].
+    /// Deleted keys and keys without a path after `prefix`, such as `prefix` itself, are
+    /// skipped, and keys that would be written outside of `root_dir` fail the export.
+    ///
+    /// `cb` receives the progress of all files, with ids unique within this export, and a
+    /// single `AllDone` at the end. Returns the number of exported files.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn export_prefix(
+        &self,
+        prefix: String,
+        root_dir: String,
+        mode: BlobExportMode,
+        cb: Option<Arc<dyn DocExportFileCallback>>,
+    ) -> Result<u64, IrohError> {
+        let query = iroh::docs::store::Query::single_latest_per_key()
+            .key_prefix(prefix.as_bytes())
+            .build();
+        let mut entries = self.inner.get_many(query).await?;
+        let root = PathBuf::from(root_dir);
+        let mode: iroh::blobs::store::ExportMode = mode.into();
+        let mut ids = IdRemap::default();
+        let mut count = 0;
+        while let Some(entry) = entries.try_next().await? {
+            // remove the prefix here, `key_to_path` panics on keys that are not longer than it
+            let key = entry.key();
+            let key = key.strip_suffix(b"\0").unwrap_or(key);
+            let Some(rest) = key.strip_prefix(prefix.as_bytes()) else {
+                continue;
+            };
+            if rest.is_empty() {
+                continue;
+            }
+            let path = iroh::util::fs::key_to_path(rest, None, Some(root.clone()))?;
+            // keys come from other peers too, never write outside of `root_dir`
+            let escapes = path
+                .components()
+                .any(|c| matches!(c, std::path::Component::ParentDir));
+            if escapes || !path.starts_with(&root) {
+                return Err(anyhow::anyhow!(
+                    "key {:?} is not a path below {}",
+                    entry.key(),
+                    root.display()
+                )
+                .into());
+            }
+            let mut stream = self.inner.export_file(entry, path, mode).await?;
+            ids.reset();
+            while let Some(progress) = stream.next().await {
+                let mut progress: DocExportProgress = progress?.into();
+                match progress {
+                    DocExportProgress::Found(DocExportProgressFound { ref mut id, .. }) => {
+                        *id = ids.found(*id);
+                    }
+                    DocExportProgress::Progress(DocExportProgressProgress {
+                        ref mut id, ..
+                    })
+                    | DocExportProgress::Done(DocExportProgressDone { ref mut id }) => {
+                        *id = ids.get(*id);
+                    }
+                    DocExportProgress::AllDone => continue,
+                    DocExportProgress::Abort(ref abort) => {
+                        let err = anyhow::anyhow!("export aborted: {}", abort.error);
+                        if let Some(ref cb) = cb {
+                            cb.progress(Arc::new(progress)).await?;
+                        }
+                        return Err(err.into());
+                    }
+                }
+                if let Some(ref cb) = cb {
+                    cb.progress(Arc::new(progress)).await?;
+                }
+            }
+            count += 1;
+        }
+        if let Some(ref cb) = cb {
+            cb.progress(Arc::new(DocExportProgress::AllDone)).await?;
+        }
+        Ok(count)
+    }
+
     /// Delete entries that match the given `author` and key `prefix`.
     ///
     /// This inserts an empty entry with the key set to `prefix`, effectively clearing all other
@@ -277,12 +521,11 @@ impl Doc {
     /// Get entries.
     ///
     /// Note: this allocates for each `Entry`, if you have many `Entry`s this may be a prohibitively large list.
-    /// Please file an [issue](https://github.com/n0-computer/iroh-ffi/issues/new) if you run into this issue
+    /// Use [`Doc::get_many_iter`] to page through them instead.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn get_many(&self, query: Arc<Query>) -> Result<Vec<Arc<Entry>>, IrohError> {
-        let entries = self
-            .inner
-            .get_many(query.0.clone())
+        let entries = query
+            .entries(&self.inner)
             .await?
             .map_ok(|e| Arc::new(Entry(e)))
             .try_collect::<Vec<_>>()
@@ -290,15 +533,40 @@ impl Doc {
         Ok(entries)
     }
 
+    /// Iterate over the entries matching `query`, in batches.
+    ///
+    /// If `after` is set, iteration starts after the entry at that cursor, as returned by
+    /// [`EntryIterator::cursor`], e.g. to resume paging after a restart. Entries inserted in
+    /// the meantime are returned if they sort after the cursor. The `offset` and `limit` of
+    /// the query count the entries returned before the cursor.
+    ///
+    /// The cursor records the position of its entry, so the node skips the entries before it
+    /// without sending them. The node still walks those entries in its store, so resuming
+    /// costs time linear in the position, but only the entries after the cursor are
+    /// transferred. If entries before the cursor were added or removed since, a few more
+    /// entries are sent and skipped here. A cursor without a position streams all entries
+    /// up to the cursor.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn get_many_iter(
+        &self,
+        query: Arc<Query>,
+        after: Option<EntryCursor>,
+    ) -> Result<Arc<EntryIterator>, IrohError> {
+        let stream = query.entries_after(&self.inner, after.clone()).await?;
+        Ok(Arc::new(EntryIterator {
+            entries: BatchStream::new(stream),
+            cursor: std::sync::Mutex::new(after),
+        }))
+    }
+
     /// Get the latest entry for a key and author.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn get_one(&self, query: Arc<Query>) -> Result<Option<Arc<Entry>>, IrohError> {
-        let res = self
-            .inner
-            .get_one((*query).clone().0)
-            .await
-            .map(|e| e.map(|e| Arc::new(e.into())))?;
-        Ok(res)
+        let res = match query.filter {
+            None => self.inner.get_one(query.store_query(0)).await?,
+            Some(_) => query.entries(&self.inner).await?.try_next().await?,
+        };
+        Ok(res.map(|e| Arc::new(e.into())))
     }
 
     /// Share this document with peers over a ticket.
@@ -338,26 +606,18 @@ impl Doc {
     }
 
     /// Subscribe to events for this document.
+    ///
+    /// Events are passed to `cb` until the returned subscription is cancelled or this `Doc` is
+    /// closed. Closing another `Doc` of the same document does not stop the subscription. An
+    /// error ends the subscription, and is passed as the last event, of type
+    /// `LiveEventType::Error`.
     #[uniffi::method(async_runtime = "tokio")]
-    pub async fn subscribe(&self, cb: Arc<dyn SubscribeCallback>) -> Result<(), IrohError> {
-        let client = self.inner.clone();
-        tokio::task::spawn(async move {
-            let mut sub = client.subscribe().await.unwrap();
-            while let Some(event) = sub.next().await {
-                match event {
-                    Ok(event) => {
-                        if let Err(err) = cb.event(Arc::new(event.into())).await {
-                            println!("cb error: {:?}", err);
-                        }
-                    }
-                    Err(err) => {
-                        println!("rpc error: {:?}", err);
-                    }
-                }
-            }
-        });
-
-        Ok(())
+    pub async fn subscribe(
+        &self,
+        cb: Arc<dyn SubscribeCallback>,
+    ) -> Result<Arc<DocSubscription>, IrohError> {
+        let stream = self.inner.subscribe().await?;
+        Ok(Arc::new(self.spawn_subscription(stream.boxed(), cb)))
     }
 
     /// Get status info for this document
@@ -730,9 +990,307 @@ impl From<SortDirection> for iroh::docs::store::SortDirection {
 
 /// Build a Query to search for an entry or entries in a doc.
 ///
-/// Use this with `QueryOptions` to determine sorting, grouping, and pagination.
+/// Use this with `QueryOptions` to determine sorting, grouping, and pagination,
+/// or use a [`QueryBuilder`] to combine several conditions.
 #[derive(Clone, Debug, uniffi::Object)]
-pub struct Query(pub(crate) iroh::docs::store::Query);
+pub struct Query {
+    /// The conditions of the query.
+    state: QueryBuilderState,
+    /// The order of the results, to resume after a cursor.
+    order: QueryOrder,
+    /// Conditions the store can not check, applied to the entries it returns.
+    filter: Option<QueryFilter>,
+}
+
+impl Query {
+    fn new(state: QueryBuilderState) -> Self {
+        let order = match state.latest_per_key {
+            true => QueryOrder::latest_per_key(Some(state.direction.clone())),
+            false => QueryOrder {
+                sort_by: state.sort_by.clone(),
+                direction: state.direction.clone(),
+                latest_per_key: false,
+            },
+        };
+        let filter = state.filter();
+        Query {
+            state,
+            order,
+            filter,
+        }
+    }
+
+    /// The query sent to the document store, skipping its first `skip` entries.
+    ///
+    /// Without a filter, the store applies the offset and limit of the query as well.
+    fn store_query(&self, skip: u64) -> iroh::docs::store::Query {
+        let (offset, limit) = match self.filter {
+            Some(_) => (skip, None),
+            None => (
+                self.state.offset + skip,
+                self.state.limit.map(|limit| limit.saturating_sub(skip)),
+            ),
+        };
+        self.state.store_query(offset, limit)
+    }
+
+    /// Stream the entries matching this query from `doc`.
+    async fn entries(
+        &self,
+        doc: &iroh::client::docs::Doc,
+    ) -> anyhow::Result<BoxStream<'static, anyhow::Result<iroh::client::docs::Entry>>> {
+        let stream = self.entries_after(doc, None).await?;
+        Ok(stream.map_ok(|entry| entry.entry).boxed())
+    }
+
+    /// Stream the entries matching this query from `doc` that come after `cursor`.
+    ///
+    /// If the cursor has a position, the store skips the entries before it, see
+    /// [`Self::resume`].
+    async fn entries_after(
+        &self,
+        doc: &iroh::client::docs::Doc,
+        cursor: Option<EntryCursor>,
+    ) -> anyhow::Result<BoxStream<'static, anyhow::Result<PositionedEntry>>> {
+        let resume = cursor.clone().filter(|cursor| cursor.store_position > 0);
+        let (skip, stream) = match resume {
+            Some(ref cursor) => self.resume(doc, cursor).await?,
+            None => (0, doc.get_many(self.store_query(0)).await?.boxed()),
+        };
+        let stream = stream
+            .enumerate()
+            .map(move |(i, entry)| entry.map(|entry| (skip + i as u64 + 1, entry)));
+
+        let stream = match self.filter.clone() {
+            // the store applied offset and limit, so positions match
+            None => stream
+                .map_ok(|(position, entry)| PositionedEntry {
+                    entry,
+                    position,
+                    store_position: position,
+                })
+                .boxed(),
+            Some(filter) => {
+                let (offset, limit) = (filter.offset, filter.limit);
+                let stream = stream
+                    .try_filter(move |(_, entry)| futures::future::ready(filter.matches(entry)));
+                match resume {
+                    // the entries up to the cursor were returned already and count to the limit
+                    Some(cursor) => {
+                        let order = self.order.clone();
+                        let returned = cursor.position;
+                        let stream = stream.try_skip_while(move |(_, entry)| {
+                            futures::future::ready(Ok(!order.is_after(&cursor, entry)))
+                        });
+                        let limit = limit.map(|limit| limit.saturating_sub(returned));
+                        window(stream, 0, limit, returned)
+                    }
+                    None => window(stream, offset, limit, 0),
+                }
+            }
+        };
+
+        let Some(cursor) = cursor else {
+            return Ok(stream);
+        };
+        let order = self.order.clone();
+        let stream = stream.try_skip_while(move |entry| {
+            futures::future::ready(Ok(!order.is_after(&cursor, &entry.entry)))
+        });
+        Ok(stream.boxed())
+    }
+
+    /// Start streaming the entries of the store at the position of `cursor`.
+    ///
+    /// Returns how many entries the store skipped. If entries before the cursor were removed
+    /// since it was taken, the first entry comes after the cursor, and this steps back until it
+    /// does not. Entries added before the cursor are skipped by the caller.
+    async fn resume(
+        &self,
+        doc: &iroh::client::docs::Doc,
+        cursor: &EntryCursor,
+    ) -> anyhow::Result<(
+        u64,
+        BoxStream<'static, anyhow::Result<iroh::client::docs::Entry>>,
+    )> {
+        let mut skip = cursor.store_position - 1;
+        let mut step = 1;
+        loop {
+            let mut stream = doc.get_many(self.store_query(skip)).await?;
+            match stream.try_next().await? {
+                Some(first) if skip == 0 || !self.order.is_after(cursor, &first) => {
+                    let stream = futures::stream::once(async move { Ok(first) }).chain(stream);
+                    return Ok((skip, stream.boxed()));
+                }
+                None if skip == 0 => return Ok((0, stream.boxed())),
+                _ => {
+                    skip = skip.saturating_sub(step);
+                    step *= 2;
+                }
+            }
+        }
+    }
+}
+
+/// An entry returned by a [`Query`], with its position in the results.
+struct PositionedEntry {
+    entry: iroh::client::docs::Entry,
+    /// The number of entries the query returned up to and including this one.
+    position: u64,
+    /// The number of entries the store returned up to and including this one.
+    store_position: u64,
+}
+
+/// Apply `offset` and `limit` to a stream of entries matching a [`Query`].
+///
+/// `returned` is the number of entries returned before the stream.
+fn window(
+    stream: impl Stream<Item = anyhow::Result<(u64, iroh::client::docs::Entry)>> + Send + 'static,
+    offset: u64,
+    limit: Option<u64>,
+    returned: u64,
+) -> BoxStream<'static, anyhow::Result<PositionedEntry>> {
+    let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+    stream
+        .skip(offset as usize)
+        .take(limit)
+        .enumerate()
+        .map(move |(i, entry)| {
+            entry.map(|(store_position, entry)| PositionedEntry {
+                entry,
+                position: returned + i as u64 + 1,
+                store_position,
+            })
+        })
+        .boxed()
+}
+
+/// The part of a [`Query`] built with a [`QueryBuilder`] that is checked on the returned entries.
+///
+/// Offset and limit move here as well, as they have to apply after filtering.
+#[derive(Clone, Debug, Default)]
+struct QueryFilter {
+    key_start: Option<Vec<u8>>,
+    key_end: Option<Vec<u8>>,
+    authors: Vec<iroh::docs::AuthorId>,
+    timestamp_min: Option<u64>,
+    timestamp_max: Option<u64>,
+    content_len_min: Option<u64>,
+    content_len_max: Option<u64>,
+    offset: u64,
+    limit: Option<u64>,
+}
+
+impl QueryFilter {
+    fn matches(&self, entry: &iroh::client::docs::Entry) -> bool {
+        let key = entry.key();
+        let (timestamp, content_len) = (entry.timestamp(), entry.content_len());
+        self.key_start
+            .as_ref()
+            .map_or(true, |start| key >= &start[..])
+            && self.key_end.as_ref().map_or(true, |end| key < &end[..])
+            && (self.authors.is_empty() || self.authors.contains(&entry.author()))
+            && self.timestamp_min.map_or(true, |min| timestamp >= min)
+            && self.timestamp_max.map_or(true, |max| timestamp <= max)
+            && self.content_len_min.map_or(true, |min| content_len >= min)
+            && self.content_len_max.map_or(true, |max| content_len <= max)
+    }
+}
+
+/// The order in which a [`Query`] returns entries.
+#[derive(Clone, Debug)]
+struct QueryOrder {
+    sort_by: SortBy,
+    direction: SortDirection,
+    /// Only one entry per key is returned, sorted by key.
+    latest_per_key: bool,
+}
+
+impl QueryOrder {
+    fn latest_per_key(direction: Option<SortDirection>) -> Self {
+        QueryOrder {
+            sort_by: SortBy::KeyAuthor,
+            direction: direction.unwrap_or_default(),
+            latest_per_key: true,
+        }
+    }
+
+    /// Whether `entry` is returned after the entry at `cursor`.
+    fn is_after(&self, cursor: &EntryCursor, entry: &iroh::client::docs::Entry) -> bool {
+        let author = entry.author();
+        let (author, key) = (author.as_bytes().as_slice(), entry.key());
+        let cursor_author = cursor.author.0.as_bytes().as_slice();
+        let ordering = match (self.latest_per_key, &self.sort_by) {
+            (true, _) => key.cmp(&cursor.key[..]),
+            (false, SortBy::AuthorKey) => (author, key).cmp(&(cursor_author, &cursor.key[..])),
+            (false, SortBy::KeyAuthor) => (key, author).cmp(&(&cursor.key[..], cursor_author)),
+        };
+        match self.direction {
+            SortDirection::Asc => ordering.is_gt(),
+            SortDirection::Desc => ordering.is_lt(),
+        }
+    }
+}
+
+/// The position of an entry in the results of a query, see [`EntryIterator::cursor`].
+///
+/// Store the author as a string, the key and the positions to resume iterating after a
+/// restart. Only cursors returned by [`EntryIterator::cursor`] resume quickly, a cursor built
+/// by hand without its positions streams the query from the start to skip the entries
+/// before it.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct EntryCursor {
+    /// The author of the entry.
+    pub author: Arc<AuthorId>,
+    /// The key of the entry.
+    pub key: Vec<u8>,
+    /// The number of entries the query returned up to and including this entry.
+    pub position: u64,
+    /// The number of entries the document store returned up to and including this entry.
+    ///
+    /// This differs from `position` if the query has conditions the store can not check.
+    /// 0 if unknown, then the query is streamed from the start to skip the entries before
+    /// the cursor.
+    pub store_position: u64,
+}
+
+/// Iterator over the entries of a document, created via [`Doc::get_many_iter`].
+#[derive(uniffi::Object)]
+pub struct EntryIterator {
+    entries: BatchStream<PositionedEntry>,
+    cursor: std::sync::Mutex<Option<EntryCursor>>,
+}
+
+#[uniffi::export]
+impl EntryIterator {
+    /// Get the next `n` entries.
+    ///
+    /// Returns an empty list once all entries have been returned.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn next_batch(&self, n: u32) -> Result<Vec<Arc<Entry>>, IrohError> {
+        let entries = self.entries.next_batch(n).await?;
+        if let Some(last) = entries.last() {
+            *self.cursor.lock().unwrap() = Some(EntryCursor {
+                author: Arc::new(AuthorId(last.entry.author())),
+                key: last.entry.key().to_vec(),
+                position: last.position,
+                store_position: last.store_position,
+            });
+        }
+        Ok(entries
+            .into_iter()
+            .map(|entry| Arc::new(Entry(entry.entry)))
+            .collect())
+    }
+
+    /// The position of the last returned entry.
+    ///
+    /// Pass it to [`Doc::get_many_iter`] to continue after that entry. Before the first
+    /// entry is returned, this is the cursor the iterator was created with.
+    pub fn cursor(&self) -> Option<EntryCursor> {
+        self.cursor.lock().unwrap().clone()
+    }
+}
 
 /// Options for sorting and pagination for using [`Query`]s.
 #[derive(Clone, Debug, Default, uniffi::Record)]
@@ -764,18 +1322,7 @@ impl Query {
     ///     limit: None
     #[uniffi::constructor]
     pub fn all(opts: Option<QueryOptions>) -> Self {
-        let mut builder = iroh::docs::store::Query::all();
-
-        if let Some(opts) = opts {
-            if opts.offset != 0 {
-                builder = builder.offset(opts.offset);
-            }
-            if opts.limit != 0 {
-                builder = builder.limit(opts.limit);
-            }
-            builder = builder.sort_by(opts.sort_by.into(), opts.direction.into());
-        }
-        Query(builder.build())
+        Query::new(QueryBuilderState::with_options(opts))
     }
 
     /// Query only the latest entry for each key, omitting older entries if the entry was written
@@ -787,28 +1334,21 @@ impl Query {
     ///     limit: None
     #[uniffi::constructor]
     pub fn single_latest_per_key(opts: Option<QueryOptions>) -> Self {
-        let mut builder = iroh::docs::store::Query::single_latest_per_key();
-
-        if let Some(opts) = opts {
-            if opts.offset != 0 {
-                builder = builder.offset(opts.offset);
-            }
-            if opts.limit != 0 {
-                builder = builder.limit(opts.limit);
-            }
-            builder = builder.sort_direction(opts.direction.into());
-        }
-        Query(builder.build())
+        Query::new(QueryBuilderState {
+            latest_per_key: true,
+            ..QueryBuilderState::with_options(opts)
+        })
     }
 
     /// Query exactly the key, but only the latest entry for it, omitting older entries if the entry was written
     /// to by multiple authors.
     #[uniffi::constructor]
     pub fn single_latest_per_key_exact(key: Vec<u8>) -> Self {
-        let builder = iroh::docs::store::Query::single_latest_per_key()
-            .key_exact(key)
-            .build();
-        Query(builder)
+        Query::new(QueryBuilderState {
+            key: KeySelector::Exact(key),
+            latest_per_key: true,
+            ..Default::default()
+        })
     }
 
     /// Query only the latest entry for each key, with this prefix, omitting older entries if the entry was written
@@ -820,17 +1360,11 @@ impl Query {
     ///     limit: None
     #[uniffi::constructor]
     pub fn single_latest_per_key_prefix(prefix: Vec<u8>, opts: Option<QueryOptions>) -> Self {
-        let mut builder = iroh::docs::store::Query::single_latest_per_key().key_prefix(prefix);
-
-        if let Some(opts) = opts {
-            if opts.offset != 0 {
-                builder = builder.offset(opts.offset);
-            }
-            if opts.limit != 0 {
-                builder = builder.limit(opts.limit);
-            }
-        }
-        Query(builder.build())
+        Query::new(QueryBuilderState {
+            key: KeySelector::Prefix(prefix),
+            latest_per_key: true,
+            ..QueryBuilderState::with_options(opts)
+        })
     }
 
     /// Query all entries for by a single author.
@@ -842,18 +1376,10 @@ impl Query {
     ///     limit: None
     #[uniffi::constructor]
     pub fn author(author: &AuthorId, opts: Option<QueryOptions>) -> Self {
-        let mut builder = iroh::docs::store::Query::author(author.0);
-
-        if let Some(opts) = opts {
-            if opts.offset != 0 {
-                builder = builder.offset(opts.offset);
-            }
-            if opts.limit != 0 {
-                builder = builder.limit(opts.limit);
-            }
-            builder = builder.sort_by(opts.sort_by.into(), opts.direction.into());
-        }
-        Query(builder.build())
+        Query::new(QueryBuilderState {
+            authors: vec![author.0],
+            ..QueryBuilderState::with_options(opts)
+        })
     }
 
     /// Query all entries that have an exact key.
@@ -865,25 +1391,20 @@ impl Query {
     ///     limit: None
     #[uniffi::constructor]
     pub fn key_exact(key: Vec<u8>, opts: Option<QueryOptions>) -> Self {
-        let mut builder = iroh::docs::store::Query::key_exact(key);
-
-        if let Some(opts) = opts {
-            if opts.offset != 0 {
-                builder = builder.offset(opts.offset);
-            }
-            if opts.limit != 0 {
-                builder = builder.limit(opts.limit);
-            }
-            builder = builder.sort_by(opts.sort_by.into(), opts.direction.into());
-        }
-        Query(builder.build())
+        Query::new(QueryBuilderState {
+            key: KeySelector::Exact(key),
+            ..QueryBuilderState::with_options(opts)
+        })
     }
 
     /// Create a Query for a single key and author.
     #[uniffi::constructor]
     pub fn author_key_exact(author: &AuthorId, key: Vec<u8>) -> Self {
-        let builder = iroh::docs::store::Query::author(author.0).key_exact(key);
-        Query(builder.build())
+        Query::new(QueryBuilderState {
+            key: KeySelector::Exact(key),
+            authors: vec![author.0],
+            ..Default::default()
+        })
     }
 
     /// Create a query for all entries with a given key prefix.
@@ -895,18 +1416,10 @@ impl Query {
     ///     limit: None
     #[uniffi::constructor]
     pub fn key_prefix(prefix: Vec<u8>, opts: Option<QueryOptions>) -> Self {
-        let mut builder = iroh::docs::store::Query::key_prefix(prefix);
-
-        if let Some(opts) = opts {
-            if opts.offset != 0 {
-                builder = builder.offset(opts.offset);
-            }
-            if opts.limit != 0 {
-                builder = builder.limit(opts.limit);
-            }
-            builder = builder.sort_by(opts.sort_by.into(), opts.direction.into());
-        }
-        Query(builder.build())
+        Query::new(QueryBuilderState {
+            key: KeySelector::Prefix(prefix),
+            ..QueryBuilderState::with_options(opts)
+        })
     }
 
     /// Create a query for all entries of a single author with a given key prefix.
@@ -921,28 +1434,322 @@ impl Query {
         prefix: Vec<u8>,
         opts: Option<QueryOptions>,
     ) -> Self {
-        let mut builder = iroh::docs::store::Query::author(author.0).key_prefix(prefix);
+        Query::new(QueryBuilderState {
+            key: KeySelector::Prefix(prefix),
+            authors: vec![author.0],
+            ..QueryBuilderState::with_options(opts)
+        })
+    }
+
+    /// Get the limit for this query (max. number of entries to emit).
+    pub fn limit(&self) -> Option<u64> {
+        self.state.limit
+    }
+
+    /// Get the offset for this query (number of entries to skip at the beginning).
+    pub fn offset(&self) -> u64 {
+        self.state.offset
+    }
+}
+
+/// Which keys a [`QueryBuilder`] selects.
+#[derive(Clone, Debug, Default)]
+enum KeySelector {
+    #[default]
+    Any,
+    Exact(Vec<u8>),
+    Prefix(Vec<u8>),
+    Range {
+        start: Option<Vec<u8>>,
+        end: Option<Vec<u8>>,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct QueryBuilderState {
+    key: KeySelector,
+    authors: Vec<iroh::docs::AuthorId>,
+    timestamp_min: Option<u64>,
+    timestamp_max: Option<u64>,
+    content_len_min: Option<u64>,
+    content_len_max: Option<u64>,
+    exclude_empty: bool,
+    latest_per_key: bool,
+    sort_by: SortBy,
+    direction: SortDirection,
+    offset: u64,
+    limit: Option<u64>,
+}
+
+impl Default for QueryBuilderState {
+    fn default() -> Self {
+        QueryBuilderState {
+            key: KeySelector::Any,
+            authors: Vec::new(),
+            timestamp_min: None,
+            timestamp_max: None,
+            content_len_min: None,
+            content_len_max: None,
+            exclude_empty: true,
+            latest_per_key: false,
+            sort_by: SortBy::default(),
+            direction: SortDirection::default(),
+            offset: 0,
+            limit: None,
+        }
+    }
+}
 
+impl QueryBuilderState {
+    /// The conditions of a query created with [`QueryOptions`].
+    fn with_options(opts: Option<QueryOptions>) -> Self {
+        let mut state = QueryBuilderState::default();
         if let Some(opts) = opts {
-            if opts.offset != 0 {
-                builder = builder.offset(opts.offset);
+            state.sort_by = opts.sort_by;
+            state.direction = opts.direction;
+            state.offset = opts.offset;
+            state.limit = (opts.limit != 0).then_some(opts.limit);
+        }
+        state
+    }
+
+    /// Apply the conditions the store can check itself.
+    fn apply<K>(
+        &self,
+        mut builder: iroh::docs::store::QueryBuilder<K>,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> iroh::docs::store::QueryBuilder<K> {
+        builder = match self.key {
+            KeySelector::Any => builder,
+            KeySelector::Exact(ref key) => builder.key_exact(key),
+            KeySelector::Prefix(ref prefix) => builder.key_prefix(prefix),
+            KeySelector::Range {
+                start: Some(ref start),
+                end: Some(ref end),
+            } => {
+                let common = start.iter().zip(end).take_while(|(a, b)| a == b).count();
+                if common > 0 {
+                    builder.key_prefix(&start[..common])
+                } else {
+                    builder
+                }
             }
-            if opts.limit != 0 {
-                builder = builder.limit(opts.limit);
+            KeySelector::Range { .. } => builder,
+        };
+        if let [author] = self.authors[..] {
+            if !self.latest_per_key {
+                builder = builder.author(author);
             }
-            builder = builder.sort_by(opts.sort_by.into(), opts.direction.into());
         }
-        Query(builder.build())
+        if !self.exclude_empty {
+            builder = builder.include_empty();
+        }
+        if offset != 0 {
+            builder = builder.offset(offset);
+        }
+        if let Some(limit) = limit {
+            builder = builder.limit(limit);
+        }
+        builder
     }
 
-    /// Get the limit for this query (max. number of entries to emit).
-    pub fn limit(&self) -> Option<u64> {
-        self.0.limit()
+    /// Build the query for the document store, returning `limit` entries from `offset`.
+    fn store_query(&self, offset: u64, limit: Option<u64>) -> iroh::docs::store::Query {
+        if self.latest_per_key {
+            let builder = iroh::docs::store::Query::single_latest_per_key();
+            self.apply(builder, offset, limit)
+                .sort_direction(self.direction.clone().into())
+                .build()
+        } else {
+            let builder = iroh::docs::store::Query::all();
+            self.apply(builder, offset, limit)
+                .sort_by(self.sort_by.clone().into(), self.direction.clone().into())
+                .build()
+        }
     }
 
-    /// Get the offset for this query (number of entries to skip at the beginning).
-    pub fn offset(&self) -> u64 {
-        self.0.offset()
+    /// The conditions the store can not check, if any.
+    fn filter(&self) -> Option<QueryFilter> {
+        let (key_start, key_end) = match self.key {
+            KeySelector::Range { ref start, ref end } => (start.clone(), end.clone()),
+            _ => (None, None),
+        };
+        let authors = match self.authors.len() {
+            1 if !self.latest_per_key => Vec::new(),
+            _ => self.authors.clone(),
+        };
+        let filter = QueryFilter {
+            key_start,
+            key_end,
+            authors,
+            timestamp_min: self.timestamp_min,
+            timestamp_max: self.timestamp_max,
+            content_len_min: self.content_len_min,
+            content_len_max: self.content_len_max,
+            offset: self.offset,
+            limit: self.limit,
+        };
+        let unfiltered = filter.key_start.is_none()
+            && filter.key_end.is_none()
+            && filter.authors.is_empty()
+            && filter.timestamp_min.is_none()
+            && filter.timestamp_max.is_none()
+            && filter.content_len_min.is_none()
+            && filter.content_len_max.is_none();
+        (!unfiltered).then_some(filter)
+    }
+}
+
+/// Build a [`Query`] out of several conditions, which all have to match.
+///
+/// Conditions the document store can not check itself, like key ranges, several authors,
+/// timestamps and content lengths, are checked on the returned entries before applying
+/// the offset and limit. Calling a key method replaces the previous key selection.
+#[derive(Debug, Default, uniffi::Object)]
+pub struct QueryBuilder(std::sync::Mutex<QueryBuilderState>);
+
+impl QueryBuilder {
+    fn update(self: Arc<Self>, f: impl FnOnce(&mut QueryBuilderState)) -> Arc<Self> {
+        f(&mut self.0.lock().unwrap());
+        self
+    }
+}
+
+#[uniffi::export]
+impl QueryBuilder {
+    /// Create a builder that matches all non-empty entries.
+    #[uniffi::constructor]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match entries with exactly this key.
+    pub fn key_exact(self: Arc<Self>, key: Vec<u8>) -> Arc<Self> {
+        self.update(|state| state.key = KeySelector::Exact(key))
+    }
+
+    /// Only match entries whose key starts with `prefix`.
+    pub fn key_prefix(self: Arc<Self>, prefix: Vec<u8>) -> Arc<Self> {
+        self.update(|state| state.key = KeySelector::Prefix(prefix))
+    }
+
+    /// Only match entries with a key in `start..end`.
+    ///
+    /// `start` is inclusive and `end` exclusive, a missing bound is unbounded.
+    pub fn key_range(self: Arc<Self>, start: Option<Vec<u8>>, end: Option<Vec<u8>>) -> Arc<Self> {
+        self.update(|state| state.key = KeySelector::Range { start, end })
+    }
+
+    /// Only match entries by this author, in addition to previously added authors.
+    pub fn author(self: Arc<Self>, author: Arc<AuthorId>) -> Arc<Self> {
+        self.update(|state| state.authors.push(author.0))
+    }
+
+    /// Only match entries by one of these authors, in addition to previously added authors.
+    pub fn authors(self: Arc<Self>, authors: Vec<Arc<AuthorId>>) -> Arc<Self> {
+        self.update(|state| state.authors.extend(authors.iter().map(|author| author.0)))
+    }
+
+    /// Only match entries with a timestamp of at least `timestamp`, in microseconds since the unix epoch.
+    pub fn timestamp_min(self: Arc<Self>, timestamp: u64) -> Arc<Self> {
+        self.update(|state| state.timestamp_min = Some(timestamp))
+    }
+
+    /// Only match entries with a timestamp of at most `timestamp`, in microseconds since the unix epoch.
+    pub fn timestamp_max(self: Arc<Self>, timestamp: u64) -> Arc<Self> {
+        self.update(|state| state.timestamp_max = Some(timestamp))
+    }
+
+    /// Only match entries with a content length of at least `len` bytes.
+    pub fn content_len_min(self: Arc<Self>, len: u64) -> Arc<Self> {
+        self.update(|state| state.content_len_min = Some(len))
+    }
+
+    /// Only match entries with a content length of at most `len` bytes.
+    pub fn content_len_max(self: Arc<Self>, len: u64) -> Arc<Self> {
+        self.update(|state| state.content_len_max = Some(len))
+    }
+
+    /// Whether to skip empty entries, which mark deleted keys.
+    ///
+    /// Default is `true`.
+    pub fn exclude_empty(self: Arc<Self>, exclude: bool) -> Arc<Self> {
+        self.update(|state| state.exclude_empty = exclude)
+    }
+
+    /// Only return the latest entry for each key, sorted by key.
+    ///
+    /// The other conditions are checked on the latest entry of each key.
+    pub fn single_latest_per_key(self: Arc<Self>, latest: bool) -> Arc<Self> {
+        self.update(|state| state.latest_per_key = latest)
+    }
+
+    /// Set the sort order. `sort_by` is ignored for [`QueryBuilder::single_latest_per_key`].
+    pub fn sort(self: Arc<Self>, sort_by: SortBy, direction: SortDirection) -> Arc<Self> {
+        self.update(|state| {
+            state.sort_by = sort_by;
+            state.direction = direction;
+        })
+    }
+
+    /// Skip this many matching entries.
+    pub fn offset(self: Arc<Self>, offset: u64) -> Arc<Self> {
+        self.update(|state| state.offset = offset)
+    }
+
+    /// Return at most this many entries.
+    ///
+    /// When the limit is 0, the limit does not exist.
+    pub fn limit(self: Arc<Self>, limit: u64) -> Arc<Self> {
+        self.update(|state| state.limit = (limit != 0).then_some(limit))
+    }
+
+    /// Build the query.
+    pub fn build(&self) -> Arc<Query> {
+        let state = self.0.lock().unwrap().clone();
+        Arc::new(Query::new(state))
+    }
+}
+
+/// A document joined via `docs.join_and_subscribe`, and the subscription to its events.
+#[derive(uniffi::Record)]
+pub struct DocAndSubscription {
+    /// The joined document.
+    pub doc: Arc<Doc>,
+    /// The subscription, passing events to the callback.
+    pub subscription: Arc<DocSubscription>,
+}
+
+/// A subscription to the events of a document, returned by `doc.subscribe`.
+#[derive(uniffi::Object)]
+pub struct DocSubscription {
+    cancel: CancellationToken,
+    /// Cancelled once the subscription stopped.
+    done: CancellationToken,
+}
+
+#[uniffi::export]
+impl DocSubscription {
+    /// Stop passing events to the callback.
+    ///
+    /// Does nothing if the subscription is already closed.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Whether the subscription stopped.
+    pub fn is_closed(&self) -> bool {
+        self.done.is_cancelled()
+    }
+
+    /// Wait until the subscription stopped, because it was cancelled, the document was
+    /// closed or the node shut down.
+    ///
+    /// Once this returns, the callback is not called anymore.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn closed(&self) {
+        self.done.cancelled().await
     }
 }
 
@@ -994,6 +1801,10 @@ pub enum LiveEvent {
     /// Receiving this event does not guarantee that all content in the document is available. If
     /// blobs failed to download, this event will still be emitted after all operations completed.
     PendingContentReady,
+    /// The subscription failed, with the error message.
+    ///
+    /// This will be the last event of the subscription.
+    Error(String),
 }
 
 /// The type of events that can be emitted during the live sync progress
@@ -1021,6 +1832,8 @@ pub enum LiveEventType {
     /// Receiving this event does not guarantee that all content in the document is available. If
     /// blobs failed to download, this event will still be emitted after all operations completed.
     PendingContentReady,
+    /// The subscription failed.
+    Error,
 }
 
 #[uniffi::export]
@@ -1035,6 +1848,7 @@ impl LiveEvent {
             Self::NeighborDown(_) => LiveEventType::NeighborDown,
             Self::SyncFinished(_) => LiveEventType::SyncFinished,
             Self::PendingContentReady => LiveEventType::PendingContentReady,
+            Self::Error(_) => LiveEventType::Error,
         }
     }
 
@@ -1100,6 +1914,15 @@ impl LiveEvent {
             panic!("not an sync event event");
         }
     }
+
+    /// For `LiveEventType::Error`, returns the error message
+    pub fn as_error(&self) -> String {
+        if let Self::Error(message) = self {
+            message.clone()
+        } else {
+            panic!("not an error event");
+        }
+    }
 }
 
 impl From<iroh::client::docs::LiveEvent> for LiveEvent {
@@ -1234,9 +2057,20 @@ impl From<iroh::docs::ContentStatus> for ContentStatus {
     }
 }
 
+/// The outcome of [`Doc::import_directory`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct DocImportDirectoryOutcome {
+    /// The number of imported files.
+    pub imported: u64,
+    /// The number of keys deleted because their file no longer exists.
+    ///
+    /// Always 0 for imports without a prefix, which do not delete keys.
+    pub deleted: u64,
+}
+
 /// The `progress` method will be called for each `DocImportProgress` event that is
-/// emitted during a `doc.import_file()` call. Use the `DocImportProgress.type()`
-/// method to check the `DocImportProgressType`
+/// emitted during a `doc.import_file()` or `doc.import_directory()` call. Use the
+/// `DocImportProgress.type()` method to check the `DocImportProgressType`
 #[uniffi::export(with_foreign)]
 #[async_trait::async_trait]
 pub trait DocImportFileCallback: Send + Sync + 'static {
@@ -1463,7 +2297,7 @@ pub struct DocExportProgressAbort {
     pub error: String,
 }
 
-/// Progress updates for the doc import file operation.
+/// Progress updates for the doc export file and blob export operations.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Object)]
 pub enum DocExportProgress {
     /// An item was found with name `name`, from now on referred to via `id`
@@ -1539,6 +2373,13 @@ impl DocExportProgress {
             _ => panic!("DocExportProgress type is not 'Progress'"),
         }
     }
+    /// Return the `DocExportProgressDone` event
+    pub fn as_done(&self) -> DocExportProgressDone {
+        match self {
+            DocExportProgress::Done(d) => d.clone(),
+            _ => panic!("DocExportProgress type is not 'Done'"),
+        }
+    }
     /// Return the `DocExportProgressAbort`
     pub fn as_abort(&self) -> DocExportProgressAbort {
         match self {
@@ -1647,11 +2488,12 @@ mod tests {
         // join the same doc from node_1
         let (found_s_1, mut found_r_1) = mpsc::channel(8);
         let cb_1 = Callback { found_s: found_s_1 };
-        let doc_1 = node_1
+        let joined = node_1
             .docs()
             .join_and_subscribe(&ticket, Arc::new(cb_1))
             .await
             .unwrap();
+        let doc_1 = joined.doc.clone();
 
         // wait for initial sync to be one
         while let Some(event) = found_r_1.recv().await {
@@ -1677,6 +2519,11 @@ mod tests {
                 break;
             }
         }
+
+        // closing the joined document stops the subscription
+        assert!(!joined.subscription.is_closed());
+        doc_1.close_me().await.unwrap();
+        joined.subscription.closed().await;
     }
 
     #[test]
@@ -1819,6 +2666,216 @@ mod tests {
         assert_eq!(val.len() as u64, entry.content_len());
     }
 
+    #[test]
+    fn test_query_builder() {
+        // conditions the store can check are not filtered afterwards
+        let query = Arc::new(QueryBuilder::new())
+            .key_prefix(b"chan/".to_vec())
+            .limit(10)
+            .build();
+        assert!(query.filter.is_none());
+        assert_eq!(Some(10), query.store_query(0).limit());
+        // skipping entries moves the window of the store
+        assert_eq!(Some(7), query.store_query(3).limit());
+        assert_eq!(3, query.store_query(3).offset());
+
+        // offset and limit apply after filtering
+        let query = Arc::new(QueryBuilder::new())
+            .key_range(Some(b"chan/a".to_vec()), Some(b"chan/b".to_vec()))
+            .timestamp_min(5)
+            .offset(2)
+            .limit(10)
+            .build();
+        assert_eq!(None, query.store_query(0).limit());
+        assert_eq!(0, query.store_query(0).offset());
+        assert_eq!(3, query.store_query(3).offset());
+        assert_eq!(Some(10), query.limit());
+        assert_eq!(2, query.offset());
+        let filter = query.filter.as_ref().unwrap();
+        assert_eq!(Some(b"chan/a".to_vec()), filter.key_start);
+        assert_eq!(Some(5), filter.timestamp_min);
+
+        // a single author is checked by the store, several are filtered
+        let mut rng = rand::thread_rng();
+        let a = Arc::new(AuthorId(iroh::docs::Author::new(&mut rng).id()));
+        let b = Arc::new(AuthorId(iroh::docs::Author::new(&mut rng).id()));
+        let query = Arc::new(QueryBuilder::new()).author(a.clone()).build();
+        assert!(query.filter.is_none());
+        let query = Arc::new(QueryBuilder::new()).authors(vec![a, b]).build();
+        assert_eq!(2, query.filter.as_ref().unwrap().authors.len());
+    }
+
+    #[tokio::test]
+    async fn test_doc_query_builder() {
+        let options = crate::NodeOptions {
+            enable_docs: true,
+            ..Default::default()
+        };
+        let node = Iroh::memory_with_options(options).await.unwrap();
+        let doc = node.docs().create().await.unwrap();
+        let alice = node.authors().create().await.unwrap();
+        let bob = node.authors().create().await.unwrap();
+        for key in ["chan/a/1", "chan/b/1", "chan/b/2", "chan/c/1"] {
+            doc.set_bytes(&alice, key.into(), b"hi".to_vec())
+                .await
+                .unwrap();
+        }
+        doc.set_bytes(&bob, b"chan/b/3".to_vec(), b"hello".to_vec())
+            .await
+            .unwrap();
+        doc.delete(alice.clone(), b"chan/b/2".to_vec())
+            .await
+            .unwrap();
+
+        let keys = |entries: Vec<Arc<Entry>>| {
+            entries
+                .iter()
+                .map(|e| String::from_utf8(e.key()).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let query = Arc::new(QueryBuilder::new())
+            .key_range(Some(b"chan/b".to_vec()), Some(b"chan/c".to_vec()))
+            .authors(vec![alice.clone(), bob.clone()])
+            .sort(SortBy::KeyAuthor, SortDirection::Asc)
+            .build();
+        let entries = doc.get_many(query).await.unwrap();
+        assert_eq!(vec!["chan/b/1", "chan/b/3"], keys(entries));
+
+        let query = Arc::new(QueryBuilder::new())
+            .key_prefix(b"chan/b".to_vec())
+            .exclude_empty(false)
+            .content_len_max(2)
+            .sort(SortBy::KeyAuthor, SortDirection::Asc)
+            .build();
+        let entries = doc.get_many(query).await.unwrap();
+        assert_eq!(vec!["chan/b/1", "chan/b/2"], keys(entries));
+
+        let query = Arc::new(QueryBuilder::new())
+            .key_prefix(b"chan/".to_vec())
+            .content_len_min(5)
+            .build();
+        let entry = doc.get_one(query).await.unwrap().unwrap();
+        assert_eq!(b"chan/b/3".to_vec(), entry.key());
+    }
+
+    #[tokio::test]
+    async fn test_doc_get_many_iter() {
+        let options = crate::NodeOptions {
+            enable_docs: true,
+            ..Default::default()
+        };
+        let node = Iroh::memory_with_options(options).await.unwrap();
+        let doc = node.docs().create().await.unwrap();
+        let author = node.authors().create().await.unwrap();
+        for i in 0..5u8 {
+            doc.set_bytes(&author, vec![i], vec![i]).await.unwrap();
+        }
+
+        let query = Arc::new(Query::all(None));
+        let iter = doc.get_many_iter(query.clone(), None).await.unwrap();
+        assert!(iter.cursor().is_none());
+        let batch = iter.next_batch(2).await.unwrap();
+        assert_eq!(
+            vec![vec![0], vec![1]],
+            batch.iter().map(|e| e.key()).collect::<Vec<_>>()
+        );
+        let cursor = iter.cursor().unwrap();
+        assert_eq!(vec![1], cursor.key);
+        assert_eq!((2, 2), (cursor.position, cursor.store_position));
+
+        // resume from the cursor with a new iterator
+        let iter = doc.get_many_iter(query, Some(cursor)).await.unwrap();
+        let batch = iter.next_batch(10).await.unwrap();
+        assert_eq!(
+            vec![vec![2], vec![3], vec![4]],
+            batch.iter().map(|e| e.key()).collect::<Vec<_>>()
+        );
+        assert!(iter.next_batch(10).await.unwrap().is_empty());
+        assert_eq!(vec![4], iter.cursor().unwrap().key);
+
+        // descending order resumes downwards
+        let opts = QueryOptions {
+            direction: SortDirection::Desc,
+            ..Default::default()
+        };
+        let query = Arc::new(Query::single_latest_per_key(Some(opts)));
+        // a cursor without a position streams the entries before it
+        let cursor = EntryCursor {
+            author: author.clone(),
+            key: vec![2],
+            position: 0,
+            store_position: 0,
+        };
+        let iter = doc.get_many_iter(query, Some(cursor)).await.unwrap();
+        let batch = iter.next_batch(10).await.unwrap();
+        assert_eq!(
+            vec![vec![1], vec![0]],
+            batch.iter().map(|e| e.key()).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_doc_get_many_iter_resume() {
+        let options = crate::NodeOptions {
+            enable_docs: true,
+            ..Default::default()
+        };
+        let node = Iroh::memory_with_options(options).await.unwrap();
+        let doc = node.docs().create().await.unwrap();
+        let author = node.authors().create().await.unwrap();
+        for i in 0..10u8 {
+            doc.set_bytes(&author, vec![i], vec![i]).await.unwrap();
+        }
+        let keys = |batch: Vec<Arc<Entry>>| batch.iter().map(|e| e.key()).collect::<Vec<_>>();
+
+        // the store skips to the position of the cursor
+        let query = Arc::new(Query::all(None));
+        let iter = doc.get_many_iter(query.clone(), None).await.unwrap();
+        iter.next_batch(5).await.unwrap();
+        let cursor = iter.cursor().unwrap();
+        assert_eq!(vec![4], cursor.key);
+        assert_eq!(4, query.store_query(cursor.store_position - 1).offset());
+
+        // entries added before the cursor are skipped
+        doc.set_bytes(&author, vec![3, 0], vec![3]).await.unwrap();
+        let iter = doc
+            .get_many_iter(query.clone(), Some(cursor.clone()))
+            .await
+            .unwrap();
+        let expected = (5..10u8).map(|i| vec![i]).collect::<Vec<_>>();
+        assert_eq!(expected, keys(iter.next_batch(10).await.unwrap()));
+
+        // entries removed before the cursor do not cause entries to be missed
+        for i in 0..3u8 {
+            doc.delete(author.clone(), vec![i]).await.unwrap();
+        }
+        let iter = doc.get_many_iter(query, Some(cursor)).await.unwrap();
+        assert_eq!(expected, keys(iter.next_batch(10).await.unwrap()));
+        // the new cursor positions match the current entries
+        let cursor = iter.cursor().unwrap();
+        assert_eq!((8, 8), (cursor.position, cursor.store_position));
+
+        // the limit of a filtered query counts the entries before the cursor
+        let query = Arc::new(QueryBuilder::new())
+            .key_range(Some(vec![4]), None)
+            .limit(4)
+            .build();
+        assert!(query.filter.is_some());
+        let iter = doc.get_many_iter(query.clone(), None).await.unwrap();
+        assert_eq!(
+            vec![vec![4], vec![5]],
+            keys(iter.next_batch(2).await.unwrap())
+        );
+        let cursor = iter.cursor().unwrap();
+        assert_eq!((2, 4), (cursor.position, cursor.store_position));
+        let iter = doc.get_many_iter(query, Some(cursor)).await.unwrap();
+        assert_eq!(
+            vec![vec![6], vec![7]],
+            keys(iter.next_batch(10).await.unwrap())
+        );
+    }
+
     #[tokio::test]
     async fn test_doc_import_export() {
         // create temp file
@@ -1875,9 +2932,323 @@ mod tests {
         let key = entry.key().to_vec();
         let out_root_str = out_root.to_string_lossy().into_owned();
         let path = crate::key_to_path(key, None, Some(out_root_str)).unwrap();
-        doc.export_file(entry, path.clone(), None).await.unwrap();
+        doc.export_file(entry, path.clone(), BlobExportMode::Copy, None)
+            .await
+            .unwrap();
 
         let got_bytes = tokio::fs::read(path).await.unwrap();
         assert_eq!(buf, got_bytes);
     }
+
+    #[tokio::test]
+    async fn test_doc_import_directory() {
+        let options = crate::NodeOptions {
+            enable_docs: true,
+            ..Default::default()
+        };
+        let node = Iroh::memory_with_options(options).await.unwrap();
+        let doc = node.docs().create().await.unwrap();
+        let author = node.authors().create().await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join("sub").join("b.txt"), "b").unwrap();
+        let root_str = root.to_string_lossy().into_owned();
+
+        struct Callback {
+            events: std::sync::Mutex<Vec<Arc<DocImportProgress>>>,
+        }
+        #[async_trait::async_trait]
+        impl DocImportFileCallback for Callback {
+            async fn progress(
+                &self,
+                progress: Arc<DocImportProgress>,
+            ) -> Result<(), CallbackError> {
+                self.events.lock().unwrap().push(progress);
+                Ok(())
+            }
+        }
+        let cb = Arc::new(Callback {
+            events: Default::default(),
+        });
+
+        let prefix = Some("files/".to_string());
+        let outcome = doc
+            .import_directory(
+                author.clone(),
+                root_str.clone(),
+                prefix.clone(),
+                false,
+                Some(cb.clone()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(2, outcome.imported);
+        assert_eq!(0, outcome.deleted);
+        {
+            let events = cb.events.lock().unwrap();
+            let found = events
+                .iter()
+                .filter(|e| matches!(e.r#type(), DocImportProgressType::Found))
+                .map(|e| e.as_found().id)
+                .collect::<Vec<_>>();
+            assert_eq!(vec![0, 1], found);
+            let last = events.last().unwrap();
+            assert_eq!(b"files/".to_vec(), last.as_all_done().key);
+        }
+
+        let b_path = root.join("sub").join("b.txt");
+        let b_key = crate::path_to_key(
+            b_path.to_string_lossy().into_owned(),
+            Some("files/".to_string()),
+            Some(root_str.clone()),
+        )
+        .unwrap();
+        assert_eq!(b"files/sub/b.txt\0".to_vec(), b_key);
+        let query = Query::author_key_exact(&author, b_key.clone());
+        let entry = doc.get_one(query.into()).await.unwrap().unwrap();
+        assert_eq!(
+            b"b".to_vec(),
+            entry.content_bytes(doc.clone()).await.unwrap()
+        );
+
+        // without a prefix, no keys are deleted
+        std::fs::remove_file(&b_path).unwrap();
+        let outcome = doc
+            .import_directory(author.clone(), root_str.clone(), None, false, None)
+            .await
+            .unwrap();
+        assert_eq!(1, outcome.imported);
+        assert_eq!(0, outcome.deleted);
+        let query = Query::author_key_exact(&author, b_key.clone());
+        assert!(doc.get_one(query.into()).await.unwrap().is_some());
+
+        // a removed file deletes its key, file keys outside of the prefix directory are kept
+        for key in [&b"other\0"[..], b"files2/c.txt\0"] {
+            doc.set_bytes(&author, key.to_vec(), b"x".to_vec())
+                .await
+                .unwrap();
+        }
+        let outcome = doc
+            .import_directory(
+                author.clone(),
+                root_str.clone(),
+                prefix.clone(),
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(1, outcome.imported);
+        assert_eq!(1, outcome.deleted);
+        let query = Query::author_key_exact(&author, b_key);
+        assert!(doc.get_one(query.into()).await.unwrap().is_none());
+        for key in [&b"other\0"[..], b"files2/c.txt\0", b"a.txt\0"] {
+            let query = Query::author_key_exact(&author, key.to_vec());
+            assert!(doc.get_one(query.into()).await.unwrap().is_some());
+        }
+
+        // deleting the key of `x` keeps the key of `xy`, which it is a prefix of without the
+        // null byte
+        std::fs::write(root.join("x"), "x").unwrap();
+        std::fs::write(root.join("xy"), "xy").unwrap();
+        let outcome = doc
+            .import_directory(
+                author.clone(),
+                root_str.clone(),
+                prefix.clone(),
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(3, outcome.imported);
+        assert_eq!(0, outcome.deleted);
+        std::fs::remove_file(root.join("x")).unwrap();
+        let outcome = doc
+            .import_directory(author.clone(), root_str, prefix, false, None)
+            .await
+            .unwrap();
+        assert_eq!(2, outcome.imported);
+        assert_eq!(1, outcome.deleted);
+        let query = Query::author_key_exact(&author, b"files/x\0".to_vec());
+        assert!(doc.get_one(query.into()).await.unwrap().is_none());
+        let query = Query::author_key_exact(&author, b"files/xy\0".to_vec());
+        let entry = doc.get_one(query.into()).await.unwrap().unwrap();
+        assert_eq!(
+            b"xy".to_vec(),
+            entry.content_bytes(doc.clone()).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_doc_import_export_directory() {
+        let options = crate::NodeOptions {
+            enable_docs: true,
+            ..Default::default()
+        };
+        let node = Iroh::memory_with_options(options).await.unwrap();
+        let doc = node.docs().create().await.unwrap();
+        let author = node.authors().create().await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join("sub").join("b.txt"), "b").unwrap();
+        let root_str = root.to_string_lossy().into_owned();
+
+        // both directions use the prefix as given, with or without a trailing `/`
+        for prefix in ["photos", "docs/"] {
+            doc.import_directory(
+                author.clone(),
+                root_str.clone(),
+                Some(prefix.to_string()),
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+            let out = tempfile::tempdir().unwrap();
+            let count = doc
+                .export_prefix(
+                    prefix.to_string(),
+                    out.path().to_string_lossy().into_owned(),
+                    BlobExportMode::Copy,
+                    None,
+                )
+                .await
+                .unwrap();
+            assert_eq!(2, count);
+            let read = |path: std::path::PathBuf| std::fs::read_to_string(path).unwrap();
+            assert_eq!("a", read(out.path().join("a.txt")));
+            assert_eq!("b", read(out.path().join("sub").join("b.txt")));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_doc_export_prefix() {
+        let options = crate::NodeOptions {
+            enable_docs: true,
+            ..Default::default()
+        };
+        let node = Iroh::memory_with_options(options).await.unwrap();
+        let doc = node.docs().create().await.unwrap();
+        let author = node.authors().create().await.unwrap();
+        for (key, value) in [
+            ("photos:a.jpg\0", "a"),
+            ("photos:trip/b.jpg\0", "b"),
+            ("photos:gone.jpg\0", "gone"),
+            ("notes:c.txt\0", "c"),
+            ("photos:", "prefix"),
+            ("photos:\0", "prefix"),
+        ] {
+            doc.set_bytes(&author, key.into(), value.into())
+                .await
+                .unwrap();
+        }
+        doc.delete(author.clone(), b"photos:gone.jpg\0".to_vec())
+            .await
+            .unwrap();
+
+        struct Callback {
+            events: std::sync::Mutex<Vec<Arc<DocExportProgress>>>,
+        }
+        #[async_trait::async_trait]
+        impl DocExportFileCallback for Callback {
+            async fn progress(
+                &self,
+                progress: Arc<DocExportProgress>,
+            ) -> Result<(), CallbackError> {
+                self.events.lock().unwrap().push(progress);
+                Ok(())
+            }
+        }
+        let cb = Arc::new(Callback {
+            events: Default::default(),
+        });
+
+        let out_root = tempfile::tempdir().unwrap();
+        let count = doc
+            .export_prefix(
+                "photos:".into(),
+                out_root.path().to_string_lossy().into_owned(),
+                BlobExportMode::Copy,
+                Some(cb.clone()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(2, count);
+        let read = |path: &[&str]| {
+            std::fs::read_to_string(
+                path.iter()
+                    .fold(out_root.path().to_path_buf(), |p, c| p.join(c)),
+            )
+        };
+        assert_eq!("a", read(&["a.jpg"]).unwrap());
+        assert_eq!("b", read(&["trip", "b.jpg"]).unwrap());
+        assert!(read(&["gone.jpg"]).is_err());
+
+        let events = cb.events.lock().unwrap();
+        let found = events
+            .iter()
+            .filter(|e| matches!(e.r#type(), DocExportProgressType::Found))
+            .map(|e| e.as_found().id)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![0, 1], found);
+        assert!(matches!(
+            events.last().unwrap().r#type(),
+            DocExportProgressType::AllDone
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_doc_subscription() {
+        let options = crate::NodeOptions {
+            enable_docs: true,
+            ..Default::default()
+        };
+        let node = Iroh::memory_with_options(options).await.unwrap();
+        let author = node.authors().create().await.unwrap();
+        let doc = node.docs().create().await.unwrap();
+
+        let (events_s, mut events_r) = mpsc::channel(8);
+        struct Callback {
+            events_s: mpsc::Sender<Arc<LiveEvent>>,
+        }
+        #[async_trait::async_trait]
+        impl SubscribeCallback for Callback {
+            async fn event(&self, event: Arc<LiveEvent>) -> Result<(), CallbackError> {
+                self.events_s.send(event).await.ok();
+                Ok(())
+            }
+        }
+        let sub = doc
+            .subscribe(Arc::new(Callback {
+                events_s: events_s.clone(),
+            }))
+            .await
+            .unwrap();
+        doc.set_bytes(&author, b"hello".to_vec(), b"world".to_vec())
+            .await
+            .unwrap();
+        let event = events_r.recv().await.unwrap();
+        assert!(matches!(event.r#type(), LiveEventType::InsertLocal));
+
+        sub.cancel();
+        sub.closed().await;
+        assert!(sub.is_closed());
+        // cancelling twice is fine
+        sub.cancel();
+
+        // closing the document stops its subscriptions
+        let sub = doc
+            .subscribe(Arc::new(Callback { events_s }))
+            .await
+            .unwrap();
+        doc.close_me().await.unwrap();
+        sub.closed().await;
+    }
 }
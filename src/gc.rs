@@ -0,0 +1,759 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime},
+};
+
+use futures::TryStreamExt;
+use iroh::blobs::{
+    store::{EntryStatus, GcConfig, MapEntry, Store},
+    util::local_pool::LocalPoolHandle,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{node::NodeState, BlobFormat, CallbackError, Hash};
+
+/// The `event` method will be called for each `GcEvent` that is emitted during a garbage
+/// collection run, both for runs started by `node.run_gc()` and for periodic runs configured
+/// via `NodeOptions.gc_interval_millis`. Use the `GcEvent.type()` method to check the
+/// `GcEventType`
+#[uniffi::export(with_foreign)]
+#[async_trait::async_trait]
+pub trait GcCallback: Send + Sync + 'static {
+    async fn event(&self, event: Arc<GcEvent>) -> Result<(), CallbackError>;
+}
+
+/// The summary of a garbage collection run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct GcReport {
+    /// The number of blobs that were kept, because they are referenced.
+    pub live_blobs: u64,
+    /// The number of blobs that were deleted.
+    pub blobs_deleted: u64,
+    /// The total size of the deleted blobs, in bytes.
+    pub bytes_freed: u64,
+    /// How long the run took.
+    pub duration: Duration,
+}
+
+/// The different types of GcEvent events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+pub enum GcEventType {
+    /// The mark phase started, collecting all referenced blobs.
+    MarkStarted,
+    /// A non critical problem was found, e.g. a tagged hash sequence that is incomplete.
+    Warning,
+    /// The mark phase is done.
+    MarkDone,
+    /// A blob was deleted in the sweep phase.
+    BlobDeleted,
+    /// The run is done.
+    Done,
+}
+
+/// A GcEvent event indicating a non critical problem
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct GcWarning {
+    /// The warning message
+    pub message: String,
+}
+
+/// A GcEvent event indicating the mark phase is done
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct GcMarkDone {
+    /// The number of blobs that are referenced, and will be kept.
+    pub live_blobs: u64,
+}
+
+/// A GcEvent event indicating a blob was deleted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct GcBlobDeleted {
+    /// The hash of the deleted blob.
+    pub hash: Arc<Hash>,
+    /// The size of the deleted blob, in bytes.
+    pub size: u64,
+}
+
+/// Events emitted during a garbage collection run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Object)]
+pub enum GcEvent {
+    /// The mark phase started, collecting all referenced blobs.
+    MarkStarted,
+    /// A non critical problem was found, e.g. a tagged hash sequence that is incomplete.
+    Warning(GcWarning),
+    /// The mark phase is done.
+    MarkDone(GcMarkDone),
+    /// A blob was deleted in the sweep phase.
+    BlobDeleted(GcBlobDeleted),
+    /// The run is done.
+    ///
+    /// This will be the last event of a run.
+    Done(GcReport),
+}
+
+#[uniffi::export]
+impl GcEvent {
+    /// Get the type of event
+    pub fn r#type(&self) -> GcEventType {
+        match self {
+            GcEvent::MarkStarted => GcEventType::MarkStarted,
+            GcEvent::Warning(_) => GcEventType::Warning,
+            GcEvent::MarkDone(_) => GcEventType::MarkDone,
+            GcEvent::BlobDeleted(_) => GcEventType::BlobDeleted,
+            GcEvent::Done(_) => GcEventType::Done,
+        }
+    }
+    /// Return the `GcWarning` event
+    pub fn as_warning(&self) -> GcWarning {
+        match self {
+            GcEvent::Warning(w) => w.clone(),
+            _ => panic!("GcEvent type is not 'Warning'"),
+        }
+    }
+    /// Return the `GcMarkDone` event
+    pub fn as_mark_done(&self) -> GcMarkDone {
+        match self {
+            GcEvent::MarkDone(m) => m.clone(),
+            _ => panic!("GcEvent type is not 'MarkDone'"),
+        }
+    }
+    /// Return the `GcBlobDeleted` event
+    pub fn as_blob_deleted(&self) -> GcBlobDeleted {
+        match self {
+            GcEvent::BlobDeleted(d) => d.clone(),
+            _ => panic!("GcEvent type is not 'BlobDeleted'"),
+        }
+    }
+    /// Return the `GcReport` of the `Done` event
+    pub fn as_done(&self) -> GcReport {
+        match self {
+            GcEvent::Done(r) => r.clone(),
+            _ => panic!("GcEvent type is not 'Done'"),
+        }
+    }
+}
+
+/// How many blobs are deleted at once.
+const GC_DELETE_BATCH: usize = 100;
+
+/// Run a single garbage collection, deleting all blobs that are neither referenced by a tag,
+/// a temp tag nor a document.
+///
+/// Mirrors the mark and sweep of the iroh gc loop, but reports what it does.
+pub(crate) async fn run_gc<S: Store>(
+    store: &S,
+    client: &iroh::client::Iroh,
+    pool: &LocalPoolHandle,
+    state: &NodeState,
+) -> anyhow::Result<GcReport> {
+    let _guard = state.gc_lock.lock().await;
+    let start = std::time::Instant::now();
+    gc_start(store, pool).await?;
+    let emit = |event: GcEvent| async {
+        if let Some(ref cb) = state.gc_callback {
+            cb.event(Arc::new(event)).await?;
+        }
+        anyhow::Ok(())
+    };
+
+    emit(GcEvent::MarkStarted).await?;
+    let mut live = BTreeSet::new();
+    if state.docs_enabled {
+        live.extend(doc_content_hashes(client).await?);
+    }
+    let mut roots = BTreeSet::new();
+    for item in store.tags().await? {
+        let (_, content) = item?;
+        roots.insert(content);
+    }
+    roots.extend(store.temp_tags());
+    for content in roots {
+        if !live.insert(content.hash) || content.format.is_raw() {
+            continue;
+        }
+        // reading from the store is not `Send`
+        let (store, hash) = (store.clone(), content.hash);
+        let children =
+            pool.try_spawn(move || async move { hash_seq_children(&store, &hash).await })?;
+        match children.await? {
+            Ok(children) => live.extend(children),
+            Err(err) => {
+                let message = format!("{}: {err:#}", content.hash);
+                emit(GcEvent::Warning(GcWarning { message })).await?;
+            }
+        }
+    }
+    emit(GcEvent::MarkDone(GcMarkDone {
+        live_blobs: live.len() as u64,
+    }))
+    .await?;
+
+    let mut dead = Vec::new();
+    for hash in store.blobs().await?.chain(store.partial_blobs().await?) {
+        let hash = hash?;
+        if live.contains(&hash) {
+            continue;
+        }
+        let size = match store.get(&hash).await? {
+            Some(entry) => entry.size().value(),
+            None => 0,
+        };
+        dead.push((hash, size));
+    }
+
+    let mut blobs_deleted = 0;
+    let mut bytes_freed = 0;
+    for batch in dead.chunks(GC_DELETE_BATCH) {
+        store
+            .delete(batch.iter().map(|(hash, _)| *hash).collect())
+            .await?;
+        for (hash, size) in batch {
+            // the store skips blobs that were protected in the meantime
+            if store.entry_status(hash).await? != EntryStatus::NotFound {
+                continue;
+            }
+            blobs_deleted += 1;
+            bytes_freed += size;
+            emit(GcEvent::BlobDeleted(GcBlobDeleted {
+                hash: Arc::new((*hash).into()),
+                size: *size,
+            }))
+            .await?;
+        }
+    }
+
+    let report = GcReport {
+        live_blobs: live.len() as u64,
+        blobs_deleted,
+        bytes_freed,
+        duration: start.elapsed(),
+    };
+    emit(GcEvent::Done(report.clone())).await?;
+    Ok(report)
+}
+
+/// Notify the store that a garbage collection run starts.
+///
+/// The fs store keeps a set of the blobs imported since the last start, which its own gc
+/// loop never deletes, and only resets that set in its `gc_start`. That method is private and
+/// called by `gc_run` alone, so we run the gc loop of the store until it asks for the
+/// protected hashes, and drop it there. This relies on `gc_run` calling `gc_start` before the
+/// first call to its `protected_cb`, as it does in iroh-blobs 0.27.
+///
+/// Resetting the set while another gc run is between its mark and sweep phase lets that
+/// sweep delete blobs imported in the meantime. All runs are therefore done by this library
+/// under `NodeState.gc_lock`, and the gc loop of the node is always disabled.
+pub(crate) async fn gc_start<S: Store>(store: &S, pool: &LocalPoolHandle) -> anyhow::Result<()> {
+    let store = store.clone();
+    // the gc loop of the store is not `Send`
+    let started = pool.try_spawn(move || async move {
+        let notify = tokio::sync::Notify::new();
+        let config = GcConfig {
+            period: Duration::ZERO,
+            done_callback: None,
+        };
+        let gc = store.gc_run(config, || {
+            let notify = &notify;
+            async move {
+                notify.notify_one();
+                std::future::pending().await
+            }
+        });
+        tokio::select! {
+            _ = gc => anyhow::bail!("the store refused to start garbage collection"),
+            _ = notify.notified() => Ok(()),
+        }
+    })?;
+    started.await?
+}
+
+/// Run [`run_gc`] every `period`, until the node shuts down.
+pub(crate) async fn gc_loop<S: Store>(
+    store: S,
+    client: iroh::client::Iroh,
+    pool: LocalPoolHandle,
+    state: Arc<NodeState>,
+    cancel: tokio_util::sync::CancellationToken,
+    period: Duration,
+) {
+    loop {
+        tokio::select! {
+            biased;
+
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(period) => {}
+        }
+        if let Err(err) = run_gc(&store, &client, &pool, &state).await {
+            tracing::warn!("garbage collection failed: {err:#}");
+        }
+    }
+}
+
+/// The content hashes of all entries of all documents.
+async fn doc_content_hashes(
+    client: &iroh::client::Iroh,
+) -> anyhow::Result<BTreeSet<iroh::blobs::Hash>> {
+    let mut hashes = BTreeSet::new();
+    let ids: Vec<_> = client.docs().list().await?.try_collect().await?;
+    for (id, _) in ids {
+        let Some(doc) = client.docs().open(id).await? else {
+            continue;
+        };
+        let mut entries = doc.get_many(iroh::docs::store::Query::all()).await?;
+        while let Some(entry) = entries.try_next().await? {
+            hashes.insert(entry.content_hash());
+        }
+    }
+    Ok(hashes)
+}
+
+/// The children of a hash sequence that is complete in the store.
+pub(crate) async fn hash_seq_children<S: Store>(
+    store: &S,
+    hash: &iroh::blobs::Hash,
+) -> anyhow::Result<Vec<iroh::blobs::Hash>> {
+    let entry = store
+        .get(hash)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("hash seq not found"))?;
+    anyhow::ensure!(entry.is_complete(), "hash seq is partial");
+    let (mut stream, _) = iroh::blobs::hashseq::parse_hash_seq(entry.data_reader().await?).await?;
+    let mut children = Vec::new();
+    while let Some(child) = stream.next().await? {
+        children.push(child);
+    }
+    Ok(children)
+}
+
+/// Chooses which content to evict when the storage quota of a node is exceeded.
+#[uniffi::export(with_foreign)]
+#[async_trait::async_trait]
+pub trait EvictionPolicy: Send + Sync + 'static {
+    /// Return the names of the tags to remove, so that at least `bytes_to_free` bytes can be
+    /// garbage collected.
+    ///
+    /// Called again with the remaining candidates if the quota is still exceeded afterwards.
+    /// Returning no tags stops the eviction.
+    async fn evict(
+        &self,
+        candidates: Vec<EvictionCandidate>,
+        bytes_to_free: u64,
+    ) -> Result<Vec<Vec<u8>>, CallbackError>;
+}
+
+/// Which content to evict when the `NodeOptions.storage_quota` is exceeded.
+///
+/// When tags were set and content was accessed is only known for the current run of the
+/// node. The built-in strategies never evict tags that existed when the node started, until
+/// their content is read or provided again.
+#[derive(derive_more::Debug, Clone, Default, uniffi::Enum)]
+pub enum EvictionStrategy {
+    /// Evict the content that was least recently read or provided first. Content that was
+    /// not accessed since it was tagged counts as accessed when it was tagged.
+    #[default]
+    LeastRecentlyUsed,
+    /// Evict the content of the oldest tags first. Tags that existed when the node started
+    /// are never evicted.
+    OldestTagFirst,
+    /// Let an `EvictionPolicy` choose the tags to evict.
+    Custom {
+        #[debug("EvictionPolicy")]
+        policy: Arc<dyn EvictionPolicy>,
+    },
+}
+
+/// Tagged content that can be evicted to stay within the storage quota.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct EvictionCandidate {
+    /// The name of the tag.
+    pub tag: Vec<u8>,
+    /// The hash of the tagged content.
+    pub hash: Arc<Hash>,
+    /// The format of the tagged content.
+    pub format: BlobFormat,
+    /// The size of the content in bytes, including the children of a hash sequence.
+    ///
+    /// Blobs shared with other content are counted for each of them. Blobs that are protected
+    /// by a `BlobLease` or referenced by a document are not counted, as evicting the content
+    /// does not free them.
+    pub size: u64,
+    /// When the tag was set, or `None` if it already existed when the node started.
+    pub tagged_at: Option<SystemTime>,
+    /// When the content was last read or provided, or `None` if it was not accessed since
+    /// the node started.
+    pub last_access: Option<SystemTime>,
+}
+
+/// When blobs were last accessed.
+///
+/// This is kept in memory only, like the times the tags were set, which the tag index of the
+/// node keeps. After a restart neither is known for the existing content.
+#[derive(Debug, Default)]
+pub(crate) struct UsageTracker {
+    accessed: HashMap<iroh::blobs::Hash, SystemTime>,
+}
+
+impl UsageTracker {
+    pub(crate) fn record_access(&mut self, hash: iroh::blobs::Hash) {
+        self.accessed.insert(hash, SystemTime::now());
+    }
+}
+
+/// How often the storage quota is checked, if the blob store was written to.
+const QUOTA_CHECK_PERIOD: Duration = Duration::from_secs(30);
+/// How often the storage quota is checked even if the blob store was not written to, e.g.
+/// to evict content whose lease expired.
+const QUOTA_FULL_CHECK_PERIOD: Duration = Duration::from_secs(600);
+
+/// Keep the blob store within `quota` bytes.
+///
+/// Unreferenced blobs are collected first. If that is not enough, tags chosen by the
+/// eviction strategy of the node are removed and the garbage collection is repeated, until
+/// the quota is met or no more tags can be evicted. Content protected by temp tags or referenced by a document is
+/// never evicted.
+pub(crate) async fn enforce_quota<S: Store>(
+    store: &S,
+    client: &iroh::client::Iroh,
+    pool: &LocalPoolHandle,
+    state: &NodeState,
+    quota: u64,
+) -> anyhow::Result<()> {
+    let mut used = used_bytes(store, pool, state).await?;
+    if used <= quota {
+        return Ok(());
+    }
+    run_gc(store, client, pool, state).await?;
+    loop {
+        used = used_bytes(store, pool, state).await?;
+        if used <= quota {
+            return Ok(());
+        }
+        // evicted tags are gone, and new ones may have been created since the last round
+        let mut tags = Vec::new();
+        for item in store.tags().await? {
+            tags.push(item?);
+        }
+        let candidates = eviction_candidates(store, client, pool, state, &tags).await?;
+        let bytes_to_free = used - quota;
+        let evict = match &state.eviction {
+            EvictionStrategy::LeastRecentlyUsed => {
+                oldest_first(candidates.clone(), bytes_to_free, |c| {
+                    c.last_access.or(c.tagged_at)
+                })
+            }
+            EvictionStrategy::OldestTagFirst => {
+                oldest_first(candidates.clone(), bytes_to_free, |c| c.tagged_at)
+            }
+            EvictionStrategy::Custom { policy } => {
+                policy.evict(candidates.clone(), bytes_to_free).await?
+            }
+        };
+        let mut evicted = 0;
+        for tag in evict {
+            // a policy can only evict candidates
+            if !candidates.iter().any(|c| c.tag == tag) {
+                continue;
+            }
+            let tag = iroh::blobs::Tag(tag.into());
+            // the tag may have been removed since the candidates were collected
+            if state.tags.get(&tag).await.is_none() {
+                continue;
+            }
+            store.set_tag(tag, None).await?;
+            evicted += 1;
+        }
+        if evicted == 0 {
+            break;
+        }
+        // the evicted content can still be referenced by another tag, in which case this
+        // frees nothing, and that tag is a candidate in the next round
+        run_gc(store, client, pool, state).await?;
+    }
+    tracing::warn!("storage quota of {quota} bytes exceeded, {used} bytes in use");
+    Ok(())
+}
+
+/// Run [`enforce_quota`] periodically, until the node shuts down.
+///
+/// Checking the quota stats every blob of the store, so rounds without writes to the store
+/// are skipped, unless the last check is [`QUOTA_FULL_CHECK_PERIOD`] ago.
+pub(crate) async fn quota_loop<S: Store>(
+    store: S,
+    client: iroh::client::Iroh,
+    pool: LocalPoolHandle,
+    state: Arc<NodeState>,
+    cancel: tokio_util::sync::CancellationToken,
+    quota: u64,
+) {
+    let mut checked = None;
+    loop {
+        tokio::select! {
+            biased;
+
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(QUOTA_CHECK_PERIOD) => {}
+        }
+        // a check that deletes blobs counts as a write, so the next round checks again
+        let writes = state.store_writes.load(Ordering::Relaxed);
+        if let Some((at, seen)) = checked {
+            if seen == writes && at + QUOTA_FULL_CHECK_PERIOD > tokio::time::Instant::now() {
+                continue;
+            }
+        }
+        checked = Some((tokio::time::Instant::now(), writes));
+        if let Err(err) = enforce_quota(&store, &client, &pool, &state, quota).await {
+            tracing::warn!("enforcing the storage quota failed: {err:#}");
+        }
+    }
+}
+
+/// The bytes used by the blob store, not counting files imported in place.
+async fn used_bytes<S: Store>(
+    store: &S,
+    pool: &LocalPoolHandle,
+    state: &NodeState,
+) -> anyhow::Result<u64> {
+    let stats = crate::blob::store_stats(store, state.blobs_dir.as_deref(), pool).await?;
+    Ok(stats.inline_bytes + stats.owned_file_bytes + stats.partial_bytes + stats.outboard_bytes)
+}
+
+/// The tagged content that is neither protected by a temp tag nor referenced by a document,
+/// and whose eviction frees some bytes.
+async fn eviction_candidates<S: Store>(
+    store: &S,
+    client: &iroh::client::Iroh,
+    pool: &LocalPoolHandle,
+    state: &NodeState,
+    tags: &[(iroh::blobs::Tag, iroh::blobs::HashAndFormat)],
+) -> anyhow::Result<Vec<EvictionCandidate>> {
+    let mut protected: BTreeSet<_> = store.temp_tags().map(|content| content.hash).collect();
+    if state.docs_enabled {
+        protected.extend(doc_content_hashes(client).await?);
+    }
+    let mut candidates = Vec::new();
+    let mut known = BTreeSet::new();
+    for (tag, content) in tags {
+        let mut hashes = vec![content.hash];
+        if content.format.is_hash_seq() {
+            let (store, hash) = (store.clone(), content.hash);
+            let children =
+                pool.try_spawn(move || async move { hash_seq_children(&store, &hash).await })?;
+            // the children of a partial hash seq are not known
+            if let Ok(children) = children.await? {
+                hashes.extend(children);
+            }
+        }
+        known.extend(hashes.iter().copied());
+        if protected.contains(&content.hash) {
+            continue;
+        }
+        let mut size = 0;
+        for hash in hashes.iter().filter(|hash| !protected.contains(*hash)) {
+            if let Some(entry) = store.get(hash).await? {
+                size += entry.size().value();
+            }
+        }
+        if size == 0 {
+            continue;
+        }
+        let tagged_at = state.tags.set_at(tag).await;
+        let usage = state.usage.lock().unwrap();
+        let last_access = hashes
+            .iter()
+            .filter_map(|hash| usage.accessed.get(hash))
+            .max()
+            .copied();
+        candidates.push(EvictionCandidate {
+            tag: tag.0.to_vec(),
+            hash: Arc::new(content.hash.into()),
+            format: content.format.into(),
+            size,
+            tagged_at,
+            last_access,
+        });
+    }
+    state
+        .usage
+        .lock()
+        .unwrap()
+        .accessed
+        .retain(|hash, _| known.contains(hash));
+    Ok(candidates)
+}
+
+/// Pick candidates by ascending `key`, until `bytes_to_free` bytes are covered.
+///
+/// Candidates without a `key` are never picked.
+fn oldest_first(
+    candidates: Vec<EvictionCandidate>,
+    bytes_to_free: u64,
+    key: impl Fn(&EvictionCandidate) -> Option<SystemTime>,
+) -> Vec<Vec<u8>> {
+    let mut candidates: Vec<_> = candidates
+        .into_iter()
+        .filter_map(|c| Some((key(&c)?, c)))
+        .collect();
+    candidates.sort_by(|(a, c), (b, d)| (a, &c.tag).cmp(&(b, &d.tag)));
+    let mut freed = 0;
+    candidates
+        .into_iter()
+        .map(|(_, c)| c)
+        .take_while(|c| {
+            let more = freed < bytes_to_free;
+            freed += c.size;
+            more
+        })
+        .map(|c| c.tag)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Iroh;
+
+    #[test]
+    fn test_oldest_first() {
+        let at = |secs: Option<u64>| secs.map(|s| SystemTime::UNIX_EPOCH + Duration::from_secs(s));
+        let candidate = |tag: &str, size, tagged_at, last_access| EvictionCandidate {
+            tag: tag.as_bytes().to_vec(),
+            hash: Arc::new(Hash::new(tag.as_bytes().to_vec())),
+            format: BlobFormat::Raw,
+            size,
+            tagged_at: at(tagged_at),
+            last_access: at(last_access),
+        };
+        let candidates = vec![
+            candidate("a", 10, Some(1), Some(30)),
+            candidate("b", 10, Some(2), Some(10)),
+            candidate("c", 10, Some(3), None),
+            // existed when the node started, and was not accessed since
+            candidate("d", 10, None, None),
+            candidate("e", 10, None, Some(40)),
+        ];
+
+        let lru = |c: &EvictionCandidate| c.last_access.or(c.tagged_at);
+        let evicted = oldest_first(candidates.clone(), 15, lru);
+        assert_eq!(evicted, vec![b"c".to_vec(), b"b".to_vec()]);
+        let evicted = oldest_first(candidates.clone(), 100, lru);
+        assert_eq!(evicted, [&b"c"[..], b"b", b"a", b"e"]);
+        let oldest = oldest_first(candidates.clone(), 10, |c| c.tagged_at);
+        assert_eq!(oldest, vec![b"a".to_vec()]);
+        let oldest = oldest_first(candidates.clone(), 100, |c| c.tagged_at);
+        assert_eq!(oldest, [&b"a"[..], b"b", b"c"]);
+        assert!(oldest_first(candidates, 0, |c| c.tagged_at).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_enforce_quota() {
+        let node = Iroh::memory_with_options(crate::NodeOptions {
+            gc_interval_millis: Some(60_000),
+            storage_quota: Some(u64::MAX),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let old = node.blobs().add_bytes(vec![1; 1000]).await.unwrap();
+        let new = node.blobs().add_bytes(vec![2; 1000]).await.unwrap();
+        let Iroh::Memory(ref n, ref store, ref state) = node else {
+            unreachable!()
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        node.blobs().read_to_bytes(old.hash.clone()).await.unwrap();
+
+        // `new` was never read, so it is evicted first
+        enforce_quota(store, n, n.local_pool_handle(), state, 1000)
+            .await
+            .unwrap();
+        let blobs = node.blobs().list().await.unwrap();
+        assert!(blobs.contains(&old.hash));
+        assert!(!blobs.contains(&new.hash));
+    }
+
+    #[tokio::test]
+    async fn test_enforce_quota_shared_content() {
+        let node = Iroh::memory_with_options(crate::NodeOptions {
+            gc_interval_millis: Some(60_000),
+            storage_quota: Some(u64::MAX),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        // two tags for the same content, the oldest and the newest, and a separate one
+        let shared = node.blobs().add_bytes(vec![1; 1000]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let separate = node.blobs().add_bytes(vec![2; 1000]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let again = node.blobs().add_bytes(vec![1; 1000]).await.unwrap();
+        assert_eq!(again.hash, shared.hash);
+        assert_ne!(again.tag, shared.tag);
+        let Iroh::Memory(ref n, ref store, ref state) = node else {
+            unreachable!()
+        };
+
+        // the first round evicts the oldest shared tag and the separate one, which only frees
+        // the separate content, so the second shared tag has to be evicted as well
+        enforce_quota(store, n, n.local_pool_handle(), state, 500)
+            .await
+            .unwrap();
+        assert!(node.tags().list().await.unwrap().is_empty());
+        let used = used_bytes(store, n.local_pool_handle(), state)
+            .await
+            .unwrap();
+        assert!(used <= 500);
+        let blobs = node.blobs().list().await.unwrap();
+        assert!(!blobs.contains(&shared.hash));
+        assert!(!blobs.contains(&separate.hash));
+    }
+
+    #[tokio::test]
+    async fn test_enforce_quota_referenced() {
+        let node = Iroh::memory_with_options(crate::NodeOptions {
+            gc_interval_millis: Some(60_000),
+            storage_quota: Some(u64::MAX),
+            enable_docs: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let author = node.authors().create().await.unwrap();
+        let doc = node.docs().create().await.unwrap();
+        let shared = node.blobs().add_bytes(vec![1; 1000]).await.unwrap();
+        doc.set_bytes(&author, b"shared".to_vec(), vec![1; 1000])
+            .await
+            .unwrap();
+        let a = node.blobs().add_bytes(vec![2; 1000]).await.unwrap();
+        let b = node.blobs().add_bytes(vec![3; 1000]).await.unwrap();
+        let Iroh::Memory(ref n, ref store, ref state) = node else {
+            unreachable!()
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        node.blobs().read_to_bytes(a.hash.clone()).await.unwrap();
+        node.blobs().read_to_bytes(b.hash.clone()).await.unwrap();
+
+        // `shared` was never read, but the document keeps it, so `a` is evicted instead
+        let quota = used_bytes(store, n.local_pool_handle(), state)
+            .await
+            .unwrap()
+            - 500;
+        enforce_quota(store, n, n.local_pool_handle(), state, quota)
+            .await
+            .unwrap();
+        let tags = node.tags().list().await.unwrap();
+        assert_eq!(tags.len(), 2);
+        assert!(tags.iter().any(|tag| tag.hash == shared.hash));
+        assert!(tags.iter().all(|tag| tag.hash != a.hash));
+        let blobs = node.blobs().list().await.unwrap();
+        assert!(blobs.contains(&shared.hash));
+        assert!(!blobs.contains(&a.hash));
+        assert!(blobs.contains(&b.hash));
+
+        // nothing else can be evicted without freeing bytes
+        enforce_quota(store, n, n.local_pool_handle(), state, 0)
+            .await
+            .unwrap();
+        let tags = node.tags().list().await.unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].hash, shared.hash);
+    }
+}
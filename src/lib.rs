@@ -3,10 +3,12 @@ mod blob;
 mod doc;
 mod endpoint;
 mod error;
+mod gc;
 mod gossip;
 mod key;
 mod net;
 mod node;
+mod store;
 mod tag;
 mod ticket;
 
@@ -15,6 +17,7 @@ pub use self::blob::*;
 pub use self::doc::*;
 pub use self::endpoint::*;
 pub use self::error::*;
+pub use self::gc::*;
 pub use self::gossip::*;
 pub use self::key::*;
 pub use self::net::*;
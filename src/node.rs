@@ -1,9 +1,21 @@
-use std::{collections::HashMap, fmt::Debug, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::PathBuf,
+    sync::{atomic::AtomicU64, Arc},
+    time::Duration,
+};
 
-use iroh::node::{FsNode, MemNode, DEFAULT_RPC_ADDR};
+use iroh::{
+    node::{DocsStorage, StorageConfig, DEFAULT_RPC_ADDR},
+    util::{fs::load_secret_key, path::IrohPaths},
+};
 
 use crate::{
-    BlobProvideEventCallback, CallbackError, Connecting, Endpoint, IrohError, NodeAddr, PublicKey,
+    gc::UsageTracker,
+    store::{IndexedStore, TagIndex},
+    BlobProvideEventCallback, CallbackError, Connecting, Endpoint, EvictionStrategy, GcCallback,
+    GcReport, IrohError, NodeAddr, PublicKey,
 };
 
 /// Stats counter
@@ -194,9 +206,31 @@ impl From<iroh::net::endpoint::ConnectionType> for ConnectionType {
 #[derive(derive_more::Debug, uniffi::Record)]
 pub struct NodeOptions {
     /// How frequently the blob store should clean up unreferenced blobs, in milliseconds.
-    /// Set to 0 to disable gc
+    /// Set to 0 to disable gc, which also disables the `storage_quota`
     #[uniffi(default = None)]
     pub gc_interval_millis: Option<u64>,
+    /// Provide a callback to receive the events of every garbage collection run.
+    #[debug("GcCallback")]
+    #[uniffi(default = None)]
+    pub gc_callback: Option<Arc<dyn GcCallback>>,
+    /// The maximum number of bytes the blob store may use.
+    ///
+    /// When exceeded, unreferenced blobs are garbage collected, and if that is not enough,
+    /// tagged content is evicted as chosen by `eviction`. Content protected by a `BlobLease`
+    /// or referenced by a document is never evicted, and eviction stops once it frees nothing.
+    /// Files imported in place are not counted.
+    ///
+    /// The quota is a soft limit: it is checked every 30 seconds if the store was written to,
+    /// so the store can grow past it in between, e.g. during a large import or download.
+    ///
+    /// Enforcing the quota deletes blobs, so it is ignored unless gc is enabled with a
+    /// `gc_interval_millis` greater than 0.
+    #[uniffi(default = None)]
+    pub storage_quota: Option<u64>,
+    /// Which content to evict when the `storage_quota` is exceeded.
+    /// Defaults to the least recently used content.
+    #[uniffi(default = None)]
+    pub eviction: Option<EvictionStrategy>,
     /// Provide a callback to hook into events when the blobs component adds and provides blobs.
     #[debug("BlobProvideEventCallback")]
     #[uniffi(default = None)]
@@ -268,6 +302,9 @@ impl Default for NodeOptions {
     fn default() -> Self {
         NodeOptions {
             gc_interval_millis: Some(0),
+            gc_callback: None,
+            storage_quota: None,
+            eviction: None,
             blob_events: None,
             enable_docs: false,
             enable_rpc: false,
@@ -312,16 +349,101 @@ pub enum NodeDiscoveryConfig {
 /// An Iroh node. Allows you to sync, store, and transfer data.
 #[derive(uniffi::Object, Debug, Clone)]
 pub enum Iroh {
-    Fs(FsNode),
-    Memory(MemNode),
+    Fs(FsNode, FsStore, Arc<NodeState>),
+    Memory(MemNode, MemStore, Arc<NodeState>),
     Client(iroh::client::Iroh),
 }
 
+/// The blob store of a persistent node.
+pub(crate) type FsStore = IndexedStore<iroh::blobs::store::fs::Store>;
+/// The blob store of an in memory node.
+pub(crate) type MemStore = IndexedStore<iroh::blobs::store::mem::Store>;
+type FsNode = iroh::node::Node<FsStore>;
+type MemNode = iroh::node::Node<MemStore>;
+
+/// State of a node spawned by this library, shared by all handles to it.
+#[derive(derive_more::Debug, Default)]
+pub struct NodeState {
+    /// Receives the events of every garbage collection run.
+    #[debug("GcCallback")]
+    pub(crate) gc_callback: Option<Arc<dyn GcCallback>>,
+    /// Whether docs are enabled. Their content is protected from garbage collection.
+    pub(crate) docs_enabled: bool,
+    /// Ensures only one garbage collection runs at a time.
+    pub(crate) gc_lock: tokio::sync::Mutex<()>,
+    /// The directory of the blob store, for persistent nodes.
+    pub(crate) blobs_dir: Option<PathBuf>,
+    /// The maximum number of bytes the blob store may use.
+    pub(crate) storage_quota: Option<u64>,
+    /// Which content to evict when the quota is exceeded.
+    pub(crate) eviction: EvictionStrategy,
+    /// When tags were created and blobs accessed, for eviction.
+    pub(crate) usage: std::sync::Mutex<UsageTracker>,
+    /// The tags of the blob store, by the hash they reference.
+    pub(crate) tags: Arc<TagIndex>,
+    /// Counts the writes to the blob store, so the storage quota is only checked after
+    /// changes.
+    pub(crate) store_writes: Arc<AtomicU64>,
+}
+
+impl NodeState {
+    fn new(options: &NodeOptions) -> Self {
+        NodeState {
+            gc_callback: options.gc_callback.clone(),
+            docs_enabled: options.enable_docs,
+            gc_lock: Default::default(),
+            blobs_dir: None,
+            storage_quota: options.storage_quota.filter(|_| Self::gc_enabled(options)),
+            eviction: options.eviction.clone().unwrap_or_default(),
+            usage: Default::default(),
+            tags: Default::default(),
+            store_writes: Default::default(),
+        }
+    }
+
+    /// Record that a blob was read or provided, if a storage quota is enforced.
+    pub(crate) fn record_access(&self, hash: iroh::blobs::Hash) {
+        if self.storage_quota.is_some() {
+            self.usage.lock().unwrap().record_access(hash);
+        }
+    }
+
+    /// Whether garbage collection is enabled, which the storage quota requires.
+    fn gc_enabled(options: &NodeOptions) -> bool {
+        if matches!(options.gc_interval_millis, Some(millis) if millis > 0) {
+            return true;
+        }
+        if options.storage_quota.is_some() {
+            tracing::warn!("gc is disabled, the storage quota is ignored");
+        }
+        false
+    }
+
+    /// The period of the garbage collection loop run by this library, if any.
+    ///
+    /// The loop always replaces the gc loop of the node: `Node::run_gc` and the storage quota
+    /// run gc as well, and the runs must not overlap, see [`crate::gc::gc_start`].
+    fn gc_period(options: &NodeOptions) -> Option<Duration> {
+        match options.gc_interval_millis {
+            Some(millis) if millis > 0 => Some(Duration::from_millis(millis)),
+            _ => None,
+        }
+    }
+}
+
 impl Iroh {
+    /// Record that a blob was read, for the eviction of least recently used content.
+    pub(crate) fn record_access(&self, hash: iroh::blobs::Hash) {
+        match self {
+            Self::Fs(_, _, state) | Self::Memory(_, _, state) => state.record_access(hash),
+            Self::Client(_) => {}
+        }
+    }
+
     pub(crate) fn inner_client(&self) -> &iroh::client::Iroh {
         match self {
-            Self::Fs(node) => node,
-            Self::Memory(node) => node,
+            Self::Fs(node, _, _) => node,
+            Self::Memory(node, _, _) => node,
             Self::Client(client) => client,
         }
     }
@@ -356,21 +478,61 @@ impl Iroh {
     ) -> Result<Self, IrohError> {
         let path = PathBuf::from(path);
 
-        let builder = iroh::node::Builder::default().persist(path).await?;
-        let builder = apply_options(builder, options).await?;
+        // Mirrors `iroh::node::Builder::persist`, but keeps a handle to the blob store.
+        let blob_dir = IrohPaths::BaoStoreDir.with_root(&path);
+        tokio::fs::create_dir_all(&blob_dir)
+            .await
+            .map_err(anyhow::Error::from)?;
+        let store = iroh::blobs::store::fs::Store::load(&blob_dir)
+            .await
+            .map_err(anyhow::Error::from)?;
+        let store = IndexedStore::new(store)
+            .await
+            .map_err(anyhow::Error::from)?;
+        let secret_key = load_secret_key(IrohPaths::SecretKey.with_root(&path)).await?;
+
+        let builder = iroh::node::Builder::with_db_and_store(
+            store.clone(),
+            DocsStorage::Disabled,
+            StorageConfig::Persistent(path),
+        )
+        .secret_key(secret_key);
+        let state = Arc::new(NodeState {
+            blobs_dir: Some(blob_dir),
+            tags: store.index().clone(),
+            store_writes: store.writes().clone(),
+            ..NodeState::new(&options)
+        });
+        let gc_period = NodeState::gc_period(&options);
+        let builder = apply_options(builder, options, &state).await?;
         let node = builder.spawn().await?;
+        spawn_background_tasks(&node, &store, &state, gc_period);
 
-        Ok(Iroh::Fs(node))
+        Ok(Iroh::Fs(node, store, state))
     }
 
     /// Create a new in memory iroh node with options.
     #[uniffi::constructor(async_runtime = "tokio")]
     pub async fn memory_with_options(options: NodeOptions) -> Result<Self, IrohError> {
-        let builder = iroh::node::Builder::default();
-        let builder = apply_options(builder, options).await?;
+        let store = IndexedStore::new(iroh::blobs::store::mem::Store::new())
+            .await
+            .map_err(anyhow::Error::from)?;
+        let builder = iroh::node::Builder::with_db_and_store(
+            store.clone(),
+            DocsStorage::Disabled,
+            StorageConfig::Mem,
+        );
+        let state = Arc::new(NodeState {
+            tags: store.index().clone(),
+            store_writes: store.writes().clone(),
+            ..NodeState::new(&options)
+        });
+        let gc_period = NodeState::gc_period(&options);
+        let builder = apply_options(builder, options, &state).await?;
         let node = builder.spawn().await?;
+        spawn_background_tasks(&node, &store, &state, gc_period);
 
-        Ok(Iroh::Memory(node))
+        Ok(Iroh::Memory(node, store, state))
     }
 
     /// Create a new iroh client, connecting to an existing node.
@@ -391,19 +553,46 @@ impl Iroh {
     }
 }
 
+/// Spawn the garbage collection and storage quota loops run by this library, if configured.
+fn spawn_background_tasks<S: iroh::blobs::store::Store>(
+    node: &iroh::node::Node<S>,
+    store: &S,
+    state: &Arc<NodeState>,
+    gc_period: Option<Duration>,
+) {
+    if let Some(period) = gc_period {
+        let gc = crate::gc::gc_loop(
+            store.clone(),
+            node.client().clone(),
+            node.local_pool_handle().clone(),
+            state.clone(),
+            node.cancel_token(),
+            period,
+        );
+        tokio::task::spawn(gc);
+    }
+    if let Some(quota) = state.storage_quota {
+        let quota = crate::gc::quota_loop(
+            store.clone(),
+            node.client().clone(),
+            node.local_pool_handle().clone(),
+            state.clone(),
+            node.cancel_token(),
+            quota,
+        );
+        tokio::task::spawn(quota);
+    }
+}
+
 async fn apply_options<S: iroh::blobs::store::Store>(
     mut builder: iroh::node::Builder<S>,
     options: NodeOptions,
+    state: &Arc<NodeState>,
 ) -> anyhow::Result<iroh::node::ProtocolBuilder<S>> {
-    if let Some(millis) = options.gc_interval_millis {
-        let policy = match millis {
-            0 => iroh::node::GcPolicy::Disabled,
-            millis => iroh::node::GcPolicy::Interval(Duration::from_millis(millis)),
-        };
-        builder = builder.gc_policy(policy);
-    }
-    if let Some(blob_events_cb) = options.blob_events {
-        builder = builder.blobs_events(BlobProvideEvents::new(blob_events_cb))
+    // periodic runs are done by our own gc loop, see `NodeState::gc_period`
+    builder = builder.gc_policy(iroh::node::GcPolicy::Disabled);
+    if options.blob_events.is_some() || state.storage_quota.is_some() {
+        builder = builder.blobs_events(BlobProvideEvents::new(options.blob_events, state.clone()))
     }
 
     if options.enable_docs {
@@ -493,6 +682,27 @@ impl Node {
         Ok(res)
     }
 
+    /// Run garbage collection now, deleting all blobs that are not referenced by a tag,
+    /// a temp tag or a document.
+    ///
+    /// Events of the run are passed to the `gc_callback` of the [`NodeOptions`], if set.
+    /// Not available for RPC clients.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn run_gc(&self) -> Result<GcReport, IrohError> {
+        let report = match self.node {
+            Iroh::Fs(ref n, ref store, ref state) => {
+                crate::gc::run_gc(store, n, n.local_pool_handle(), state).await?
+            }
+            Iroh::Memory(ref n, ref store, ref state) => {
+                crate::gc::run_gc(store, n, n.local_pool_handle(), state).await?
+            }
+            Iroh::Client(_) => {
+                return Err(anyhow::anyhow!("gc is not available for RPC clients").into())
+            }
+        };
+        Ok(report)
+    }
+
     /// Shutdown this iroh node.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn shutdown(&self, force: bool) -> Result<(), IrohError> {
@@ -504,8 +714,8 @@ impl Node {
     #[uniffi::method]
     pub fn my_rpc_addr(&self) -> Option<String> {
         let addr = match self.node {
-            Iroh::Fs(ref n) => n.my_rpc_addr(),
-            Iroh::Memory(ref n) => n.my_rpc_addr(),
+            Iroh::Fs(ref n, _, _) => n.my_rpc_addr(),
+            Iroh::Memory(ref n, _, _) => n.my_rpc_addr(),
             Iroh::Client(_) => None, // Not available currently
         };
         addr.map(|a| a.to_string())
@@ -514,8 +724,8 @@ impl Node {
     #[uniffi::method]
     pub fn endpoint(&self) -> Endpoint {
         match self.node {
-            Iroh::Fs(ref n) => Endpoint::new(n.endpoint().clone()),
-            Iroh::Memory(ref n) => Endpoint::new(n.endpoint().clone()),
+            Iroh::Fs(ref n, _, _) => Endpoint::new(n.endpoint().clone()),
+            Iroh::Memory(ref n, _, _) => Endpoint::new(n.endpoint().clone()),
             Iroh::Client(_) => panic!("not available"), // Not yet available
         }
     }
@@ -560,7 +770,8 @@ impl NodeStatus {
 
 #[derive(Clone)]
 struct BlobProvideEvents {
-    callback: Arc<dyn BlobProvideEventCallback>,
+    callback: Option<Arc<dyn BlobProvideEventCallback>>,
+    state: Arc<NodeState>,
 }
 
 impl Debug for BlobProvideEvents {
@@ -570,30 +781,47 @@ impl Debug for BlobProvideEvents {
 }
 
 impl BlobProvideEvents {
-    fn new(callback: Arc<dyn BlobProvideEventCallback>) -> Self {
-        Self { callback }
+    fn new(callback: Option<Arc<dyn BlobProvideEventCallback>>, state: Arc<NodeState>) -> Self {
+        Self { callback, state }
+    }
+
+    /// Providing a blob counts as an access, for the eviction of least recently used content.
+    fn record_access(&self, event: &iroh::blobs::provider::Event) {
+        use iroh::blobs::provider::Event;
+        match event {
+            Event::GetRequestReceived { hash, .. } | Event::TransferBlobCompleted { hash, .. } => {
+                self.state.record_access(*hash)
+            }
+            _ => {}
+        }
     }
 }
 
 impl iroh::blobs::provider::CustomEventSender for BlobProvideEvents {
     fn send(&self, event: iroh::blobs::provider::Event) -> futures_lite::future::Boxed<()> {
+        self.record_access(&event);
         let cb = self.callback.clone();
         Box::pin(async move {
-            cb.blob_event(Arc::new(event.into())).await.ok();
+            if let Some(cb) = cb {
+                cb.blob_event(Arc::new(event.into())).await.ok();
+            }
         })
     }
 
     fn try_send(&self, event: iroh::blobs::provider::Event) {
-        let cb = self.callback.clone();
-        tokio::task::spawn(async move {
-            cb.blob_event(Arc::new(event.into())).await.ok();
-        });
+        self.record_access(&event);
+        if let Some(cb) = self.callback.clone() {
+            tokio::task::spawn(async move {
+                cb.blob_event(Arc::new(event.into())).await.ok();
+            });
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{GcEvent, GcEventType};
 
     #[tokio::test]
     async fn test_memory() {
@@ -614,4 +842,92 @@ mod tests {
         let node_id_client = client.net().node_id().await.unwrap();
         assert_eq!(node_id, node_id_client);
     }
+
+    #[tokio::test]
+    async fn test_storage_quota_gc_disabled() {
+        let quota = |gc_interval_millis| NodeOptions {
+            gc_interval_millis,
+            storage_quota: Some(1000),
+            ..Default::default()
+        };
+        for (opts, enforced) in [
+            (quota(None), false),
+            (quota(Some(0)), false),
+            (quota(Some(60_000)), true),
+        ] {
+            let Iroh::Memory(_, _, state) = Iroh::memory_with_options(opts).await.unwrap() else {
+                unreachable!()
+            };
+            assert_eq!(state.storage_quota.is_some(), enforced);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_gc() {
+        struct Events(std::sync::Mutex<Vec<GcEventType>>);
+
+        #[async_trait::async_trait]
+        impl GcCallback for Events {
+            async fn event(&self, event: Arc<GcEvent>) -> Result<(), CallbackError> {
+                self.0.lock().unwrap().push(event.r#type());
+                Ok(())
+            }
+        }
+
+        let events = Arc::new(Events(Default::default()));
+        let opts = NodeOptions {
+            gc_callback: Some(events.clone()),
+            ..Default::default()
+        };
+        let node = Iroh::memory_with_options(opts).await.unwrap();
+
+        let kept = node
+            .blobs()
+            .add_bytes_named(b"kept".to_vec(), "kept".into())
+            .await
+            .unwrap();
+        let dropped = node.blobs().add_bytes(b"dropped".to_vec()).await.unwrap();
+        node.tags()
+            .delete(Arc::new(crate::Tag::from_bytes(dropped.tag)))
+            .await
+            .unwrap();
+
+        let report = node.node().run_gc().await.unwrap();
+        assert_eq!(report.blobs_deleted, 1);
+        assert_eq!(report.bytes_freed, dropped.size);
+
+        let blobs = node.blobs().list().await.unwrap();
+        assert!(blobs.contains(&kept.hash));
+        assert!(!blobs.contains(&dropped.hash));
+
+        let events = events.0.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            vec![
+                GcEventType::MarkStarted,
+                GcEventType::MarkDone,
+                GcEventType::BlobDeleted,
+                GcEventType::Done,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_gc_persistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = Iroh::persistent(dir.path().to_string_lossy().into_owned())
+            .await
+            .unwrap();
+
+        // blobs imported in this session are protected by the store until a gc run starts
+        let dropped = node.blobs().add_bytes(b"dropped".to_vec()).await.unwrap();
+        node.tags()
+            .delete(Arc::new(crate::Tag::from_bytes(dropped.tag)))
+            .await
+            .unwrap();
+
+        let report = node.node().run_gc().await.unwrap();
+        assert_eq!(report.blobs_deleted, 1);
+        assert!(!node.blobs().list().await.unwrap().contains(&dropped.hash));
+    }
 }
@@ -0,0 +1,594 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    future::Future,
+    io,
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+use bao_tree::BaoTree;
+
+use bytes::Bytes;
+use futures::Stream;
+use iroh::blobs::{
+    store::{
+        fs::InlineOptions, ConsistencyCheckProgress, DbIter, EntryStatus, ExportMode,
+        ExportProgressCb, GcConfig, ImportMode, ImportProgress, Map, MapEntry, MapMut,
+        ReadableStore, Store, ValidateProgress,
+    },
+    util::{
+        progress::{BoxedProgressSender, IdGenerator, ProgressSender},
+        Tag,
+    },
+    BlobFormat, Hash, HashAndFormat, TempTag, IROH_BLOCK_SIZE,
+};
+use iroh_io::AsyncSliceReader;
+use tokio::io::AsyncRead;
+
+/// The tags of a blob store, indexed by the hash they reference.
+///
+/// Kept up to date by [`IndexedStore`], which all tag writes of a node go through.
+#[derive(Debug, Default)]
+pub(crate) struct TagIndex(tokio::sync::Mutex<TagIndexInner>);
+
+#[derive(Debug, Default)]
+struct TagIndexInner {
+    tags: BTreeMap<Tag, HashAndFormat>,
+    by_hash: HashMap<Hash, BTreeSet<Tag>>,
+    /// When the tags written since the index was loaded were set.
+    set_at: BTreeMap<Tag, SystemTime>,
+}
+
+impl TagIndexInner {
+    fn set(&mut self, name: Tag, value: Option<HashAndFormat>) {
+        match value {
+            Some(_) => self.set_at.insert(name.clone(), SystemTime::now()),
+            None => self.set_at.remove(&name),
+        };
+        if let Some(old) = self.tags.remove(&name) {
+            if let Some(names) = self.by_hash.get_mut(&old.hash) {
+                names.remove(&name);
+                if names.is_empty() {
+                    self.by_hash.remove(&old.hash);
+                }
+            }
+        }
+        if let Some(value) = value {
+            self.by_hash
+                .entry(value.hash)
+                .or_default()
+                .insert(name.clone());
+            self.tags.insert(name, value);
+        }
+    }
+}
+
+impl TagIndex {
+    /// Build the index from the tags currently in `store`.
+    async fn load<S: ReadableStore>(store: &S) -> io::Result<Self> {
+        let mut inner = TagIndexInner::default();
+        for tag in store.tags().await? {
+            let (name, value) = tag?;
+            inner.set(name, Some(value));
+        }
+        // when the existing tags were set is not known
+        inner.set_at.clear();
+        Ok(Self(tokio::sync::Mutex::new(inner)))
+    }
+
+    /// Build a snapshot of `tags`, e.g. as listed from a node over RPC.
+    ///
+    /// Unlike the index of an [`IndexedStore`], this is not kept up to date.
+    pub(crate) fn snapshot(tags: impl IntoIterator<Item = (Tag, HashAndFormat)>) -> Self {
+        let mut inner = TagIndexInner::default();
+        for (name, value) in tags {
+            inner.set(name, Some(value));
+        }
+        inner.set_at.clear();
+        Self(tokio::sync::Mutex::new(inner))
+    }
+
+    /// When the tag named `name` was last set, if that happened since the index was loaded,
+    /// i.e. since the node started.
+    pub(crate) async fn set_at(&self, name: &Tag) -> Option<SystemTime> {
+        self.0.lock().await.set_at.get(name).copied()
+    }
+
+    /// The tag named `name`, if it exists.
+    pub(crate) async fn get(&self, name: &Tag) -> Option<HashAndFormat> {
+        self.0.lock().await.tags.get(name).copied()
+    }
+
+    /// The tags with a name in `range`, ordered by name.
+    pub(crate) async fn range(&self, range: (Bound<Tag>, Bound<Tag>)) -> Vec<(Tag, HashAndFormat)> {
+        let inner = self.0.lock().await;
+        inner
+            .tags
+            .range(range)
+            .map(|(name, value)| (name.clone(), *value))
+            .collect()
+    }
+
+    /// The tags with a name starting with `prefix`, ordered by name.
+    pub(crate) async fn prefix(&self, prefix: &[u8]) -> Vec<(Tag, HashAndFormat)> {
+        let inner = self.0.lock().await;
+        let start = Tag(Bytes::copy_from_slice(prefix));
+        inner
+            .tags
+            .range(start..)
+            .take_while(|(name, _)| name.0.starts_with(prefix))
+            .map(|(name, value)| (name.clone(), *value))
+            .collect()
+    }
+
+    /// The names of all tags that reference `hash`, in order.
+    pub(crate) async fn tags_for(&self, hash: &Hash) -> Vec<Tag> {
+        let inner = self.0.lock().await;
+        inner
+            .by_hash
+            .get(hash)
+            .map(|names| names.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// A blob store that keeps a [`TagIndex`] of its tags.
+///
+/// Tag writes are serialized, so the index always matches the tags of the store. Writes of
+/// any kind are counted, see [`IndexedStore::writes`].
+#[derive(Debug, Clone)]
+pub struct IndexedStore<S> {
+    inner: S,
+    index: Arc<TagIndex>,
+    writes: Arc<AtomicU64>,
+}
+
+impl<S: Store> IndexedStore<S> {
+    /// Wrap `inner`, indexing its existing tags.
+    pub(crate) async fn new(inner: S) -> io::Result<Self> {
+        let index = Arc::new(TagIndex::load(&inner).await?);
+        Ok(Self {
+            inner,
+            index,
+            writes: Default::default(),
+        })
+    }
+
+    /// The index of the tags of this store.
+    pub(crate) fn index(&self) -> &Arc<TagIndex> {
+        &self.index
+    }
+
+    /// Counts the calls that can change the content or the tags of this store.
+    ///
+    /// The count only grows, so comparing it tells whether the store may have changed.
+    pub(crate) fn writes(&self) -> &Arc<AtomicU64> {
+        &self.writes
+    }
+
+    /// Rename the tag `from` to `to`.
+    ///
+    /// No other tags are written in the meantime. Fails if there is no tag named `from`, or
+    /// if there already is a tag named `to`. If `from` can not be removed, `to` is removed
+    /// again.
+    pub(crate) async fn rename_tag(&self, from: Tag, to: Tag) -> anyhow::Result<()> {
+        let mut index = self.index.0.lock().await;
+        if index.tags.contains_key(&to) {
+            anyhow::bail!("tag {} already exists", crate::Tag::from(to));
+        }
+        let Some(value) = index.tags.get(&from).copied() else {
+            anyhow::bail!("tag {} does not exist", crate::Tag::from(from));
+        };
+        self.count_write();
+        self.inner.set_tag(to.clone(), Some(value)).await?;
+        if let Err(err) = self.inner.set_tag(from.clone(), None).await {
+            self.inner.set_tag(to, None).await?;
+            return Err(err.into());
+        }
+        let set_at = index.set_at.get(&from).copied();
+        index.set(to.clone(), Some(value));
+        index.set(from, None);
+        // a renamed tag keeps its age
+        match set_at {
+            Some(set_at) => index.set_at.insert(to, set_at),
+            None => index.set_at.remove(&to),
+        };
+        Ok(())
+    }
+}
+
+impl<S> IndexedStore<S> {
+    fn count_write(&self) {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl<S: Map> Map for IndexedStore<S> {
+    type Entry = S::Entry;
+
+    fn get(&self, hash: &Hash) -> impl Future<Output = io::Result<Option<Self::Entry>>> + Send {
+        self.inner.get(hash)
+    }
+}
+
+impl<S: MapMut> MapMut for IndexedStore<S> {
+    type EntryMut = S::EntryMut;
+
+    fn get_mut(
+        &self,
+        hash: &Hash,
+    ) -> impl Future<Output = io::Result<Option<Self::EntryMut>>> + Send {
+        self.count_write();
+        self.inner.get_mut(hash)
+    }
+
+    fn get_or_create(
+        &self,
+        hash: Hash,
+        size: u64,
+    ) -> impl Future<Output = io::Result<Self::EntryMut>> + Send {
+        self.count_write();
+        self.inner.get_or_create(hash, size)
+    }
+
+    fn entry_status(&self, hash: &Hash) -> impl Future<Output = io::Result<EntryStatus>> + Send {
+        self.inner.entry_status(hash)
+    }
+
+    fn entry_status_sync(&self, hash: &Hash) -> io::Result<EntryStatus> {
+        self.inner.entry_status_sync(hash)
+    }
+
+    fn insert_complete(
+        &self,
+        entry: Self::EntryMut,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.count_write();
+        self.inner.insert_complete(entry)
+    }
+}
+
+impl<S: ReadableStore> ReadableStore for IndexedStore<S> {
+    fn blobs(&self) -> impl Future<Output = io::Result<DbIter<Hash>>> + Send {
+        self.inner.blobs()
+    }
+
+    fn tags(&self) -> impl Future<Output = io::Result<DbIter<(Tag, HashAndFormat)>>> + Send {
+        self.inner.tags()
+    }
+
+    fn temp_tags(&self) -> Box<dyn Iterator<Item = HashAndFormat> + Send + Sync + 'static> {
+        self.inner.temp_tags()
+    }
+
+    fn consistency_check(
+        &self,
+        repair: bool,
+        tx: BoxedProgressSender<ConsistencyCheckProgress>,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.consistency_check(repair, tx)
+    }
+
+    fn partial_blobs(&self) -> impl Future<Output = io::Result<DbIter<Hash>>> + Send {
+        self.inner.partial_blobs()
+    }
+
+    fn export(
+        &self,
+        hash: Hash,
+        target: PathBuf,
+        mode: ExportMode,
+        progress: ExportProgressCb,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.export(hash, target, mode, progress)
+    }
+}
+
+impl<S: Store> Store for IndexedStore<S> {
+    fn import_file(
+        &self,
+        data: PathBuf,
+        mode: ImportMode,
+        format: BlobFormat,
+        progress: impl ProgressSender<Msg = ImportProgress> + IdGenerator,
+    ) -> impl Future<Output = io::Result<(TempTag, u64)>> + Send {
+        self.count_write();
+        self.inner.import_file(data, mode, format, progress)
+    }
+
+    fn import_bytes(
+        &self,
+        bytes: Bytes,
+        format: BlobFormat,
+    ) -> impl Future<Output = io::Result<TempTag>> + Send {
+        self.count_write();
+        self.inner.import_bytes(bytes, format)
+    }
+
+    fn import_stream(
+        &self,
+        data: impl Stream<Item = io::Result<Bytes>> + Send + Unpin + 'static,
+        format: BlobFormat,
+        progress: impl ProgressSender<Msg = ImportProgress> + IdGenerator,
+    ) -> impl Future<Output = io::Result<(TempTag, u64)>> + Send {
+        self.count_write();
+        self.inner.import_stream(data, format, progress)
+    }
+
+    fn import_reader(
+        &self,
+        data: impl AsyncRead + Send + Unpin + 'static,
+        format: BlobFormat,
+        progress: impl ProgressSender<Msg = ImportProgress> + IdGenerator,
+    ) -> impl Future<Output = io::Result<(TempTag, u64)>> + Send {
+        self.count_write();
+        self.inner.import_reader(data, format, progress)
+    }
+
+    async fn set_tag(&self, name: Tag, value: Option<HashAndFormat>) -> io::Result<()> {
+        let mut index = self.index.0.lock().await;
+        self.count_write();
+        self.inner.set_tag(name.clone(), value).await?;
+        index.set(name, value);
+        Ok(())
+    }
+
+    async fn create_tag(&self, value: HashAndFormat) -> io::Result<Tag> {
+        let mut index = self.index.0.lock().await;
+        self.count_write();
+        let name = self.inner.create_tag(value).await?;
+        index.set(name.clone(), Some(value));
+        Ok(name)
+    }
+
+    fn temp_tag(&self, value: HashAndFormat) -> TempTag {
+        self.inner.temp_tag(value)
+    }
+
+    fn gc_run<G, Gut>(&self, config: GcConfig, protected_cb: G) -> impl Future<Output = ()>
+    where
+        G: Fn() -> Gut,
+        Gut: Future<Output = BTreeSet<Hash>> + Send,
+    {
+        self.inner.gc_run(config, protected_cb)
+    }
+
+    fn delete(&self, hashes: Vec<Hash>) -> impl Future<Output = io::Result<()>> + Send {
+        self.count_write();
+        self.inner.delete(hashes)
+    }
+
+    fn shutdown(&self) -> impl Future<Output = ()> + Send {
+        self.inner.shutdown()
+    }
+
+    fn sync(&self) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.sync()
+    }
+
+    fn validate(
+        &self,
+        repair: bool,
+        tx: BoxedProgressSender<ValidateProgress>,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.inner.validate(repair, tx)
+    }
+}
+
+/// Where the data or the outboard of a blob is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Location {
+    /// Nothing is stored, e.g. for the outboard of a blob of at most one chunk group.
+    None,
+    /// In memory, or in the database of a persistent store, with its size in bytes.
+    Inline(u64),
+    /// In a file owned by the store, with its size in bytes.
+    Owned(u64),
+    /// In a file outside the store that was imported in place, with its size in bytes.
+    Reference(u64),
+    /// Not where the store keeps it, and not readable through the store either, e.g. because
+    /// its file was deleted or truncated, with the size of the blob in bytes.
+    Unknown(u64),
+}
+
+/// Where the fs store of a persistent node keeps the data of its blobs.
+///
+/// The store does not expose this, so all assumptions about its layout are kept here:
+/// data and outboards of complete blobs up to the default inline limits are stored in the
+/// database, larger ones in `data/<hash>.data` and `data/<hash>.obao4` below the blobs
+/// directory, unless the data was imported in place. Partial blobs always use these files,
+/// once they are large enough to be written to disk. `test_fs_layout` checks these
+/// assumptions against the files of a real store.
+#[derive(Debug, Clone)]
+pub(crate) struct FsLayout {
+    data_dir: PathBuf,
+    inline: InlineOptions,
+}
+
+impl FsLayout {
+    /// The layout of the store created by `fs::Store::load(blobs_dir)`.
+    pub(crate) fn new(blobs_dir: &Path) -> Self {
+        FsLayout {
+            data_dir: blobs_dir.join("data"),
+            inline: InlineOptions::default(),
+        }
+    }
+
+    fn data_path(&self, hash: &Hash) -> PathBuf {
+        self.data_dir.join(format!("{}.data", hash.to_hex()))
+    }
+
+    fn outboard_path(&self, hash: &Hash) -> PathBuf {
+        self.data_dir.join(format!("{}.obao4", hash.to_hex()))
+    }
+
+    /// Locate the data and outboard of the complete blob `entry`.
+    ///
+    /// Data that is neither inline nor in the data directory was imported in place, if the
+    /// store can still read it through `entry`. Otherwise it is [`Location::Unknown`].
+    pub(crate) async fn complete(&self, entry: &impl MapEntry) -> io::Result<(Location, Location)> {
+        let hash = entry.hash();
+        let size = entry.size().value();
+        let data = if size <= self.inline.max_data_inlined {
+            Location::Inline(size)
+        } else {
+            match file_size(self.data_path(&hash)).await? {
+                Some(len) => Location::Owned(len),
+                None if data_readable(entry, size).await => Location::Reference(size),
+                None => Location::Unknown(size),
+            }
+        };
+        let outboard = match BaoTree::new(size, IROH_BLOCK_SIZE).outboard_size() {
+            0 => Location::None,
+            len if len <= self.inline.max_outboard_inlined => Location::Inline(len),
+            _ => file_size(self.outboard_path(&hash))
+                .await?
+                .map_or(Location::None, Location::Owned),
+        };
+        Ok((data, outboard))
+    }
+
+    /// Locate the data and outboard of a partial blob.
+    pub(crate) async fn partial(&self, hash: &Hash) -> io::Result<(Location, Location)> {
+        let data = file_size(self.data_path(hash)).await?;
+        let outboard = file_size(self.outboard_path(hash)).await?;
+        Ok((
+            data.map_or(Location::None, Location::Owned),
+            outboard.map_or(Location::None, Location::Owned),
+        ))
+    }
+}
+
+/// Whether the store can read the last byte of the `size` bytes of data of `entry`.
+async fn data_readable(entry: &impl MapEntry, size: u64) -> bool {
+    let Ok(mut reader) = entry.data_reader().await else {
+        return false;
+    };
+    matches!(reader.read_at(size - 1, 1).await, Ok(bytes) if bytes.len() == 1)
+}
+
+/// The size of the file at `path`, if it exists.
+async fn file_size(path: PathBuf) -> io::Result<Option<u64>> {
+    match tokio::fs::metadata(path).await {
+        Ok(meta) => Ok(Some(meta.len())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use iroh::blobs::{
+        store::{fs, ImportMode},
+        util::progress::IgnoreProgressSender,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tag_index_set_at() {
+        let inner = iroh::blobs::store::mem::Store::new();
+        let content = HashAndFormat::raw(Hash::new(b"content"));
+        let old = Tag::from("old");
+        inner.set_tag(old.clone(), Some(content)).await.unwrap();
+
+        // tags that existed before the store was wrapped have no known age
+        let store = IndexedStore::new(inner).await.unwrap();
+        assert_eq!(store.index().set_at(&old).await, None);
+
+        let new = Tag::from("new");
+        store.set_tag(new.clone(), Some(content)).await.unwrap();
+        let set_at = store.index().set_at(&new).await.unwrap();
+        let renamed = Tag::from("renamed");
+        store
+            .rename_tag(new.clone(), renamed.clone())
+            .await
+            .unwrap();
+        assert_eq!(store.index().set_at(&renamed).await, Some(set_at));
+        assert_eq!(store.index().set_at(&new).await, None);
+        store.rename_tag(old, new.clone()).await.unwrap();
+        assert_eq!(store.index().set_at(&new).await, None);
+        store.set_tag(renamed.clone(), None).await.unwrap();
+        assert_eq!(store.index().set_at(&renamed).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_fs_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs_dir = dir.path().join("blobs");
+        let store = fs::Store::load(&blobs_dir).await.unwrap();
+
+        let mut tags = Vec::new();
+        for size in [100, 20_000, 5_000_000] {
+            let tag = store
+                .import_bytes(vec![size as u8; size].into(), BlobFormat::Raw)
+                .await
+                .unwrap();
+            tags.push((tag, size as u64));
+        }
+        for (name, len) in [("reference", 50_000), ("truncated", 60_000)] {
+            let path = dir.path().join(name);
+            tokio::fs::write(&path, vec![7u8; len]).await.unwrap();
+            let (tag, size) = store
+                .import_file(
+                    path,
+                    ImportMode::TryReference,
+                    BlobFormat::Raw,
+                    IgnoreProgressSender::default(),
+                )
+                .await
+                .unwrap();
+            tags.push((tag, size));
+        }
+        store.sync().await.unwrap();
+        // the store can not tell that the file changed, but can no longer read all of it
+        std::fs::File::options()
+            .write(true)
+            .open(dir.path().join("truncated"))
+            .unwrap()
+            .set_len(1_000)
+            .unwrap();
+
+        let layout = FsLayout::new(&blobs_dir);
+        let mut locations = Vec::new();
+        let mut expected = BTreeMap::new();
+        for (tag, _) in &tags {
+            let hash = tag.hash();
+            let entry = store.get(hash).await.unwrap().unwrap();
+            let (data, outboard) = layout.complete(&entry).await.unwrap();
+            if let Location::Owned(len) = data {
+                expected.insert(format!("{}.data", hash.to_hex()), len);
+            }
+            if let Location::Owned(len) = outboard {
+                expected.insert(format!("{}.obao4", hash.to_hex()), len);
+            }
+            locations.push((data, outboard));
+        }
+        assert_eq!(
+            locations,
+            vec![
+                (Location::Inline(100), Location::None),
+                (Location::Owned(20_000), Location::Inline(64)),
+                (Location::Owned(5_000_000), Location::Owned(19_520)),
+                (Location::Reference(50_000), Location::Inline(192)),
+                (Location::Unknown(60_000), Location::Inline(192)),
+            ]
+        );
+
+        // the files in the data directory are exactly the ones the layout predicts
+        let mut files = BTreeMap::new();
+        let mut entries = tokio::fs::read_dir(blobs_dir.join("data")).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            let len = entry.metadata().await.unwrap().len();
+            files.insert(entry.file_name().into_string().unwrap(), len);
+        }
+        assert_eq!(files, expected);
+    }
+}
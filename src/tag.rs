@@ -1,6 +1,6 @@
-use std::sync::Arc;
+use std::{ops::Bound, sync::Arc};
 
-use crate::{BlobFormat, Hash, Iroh, IrohError};
+use crate::{blob::BatchStream, store::TagIndex, BlobFormat, Hash, Iroh, IrohError};
 use bytes::Bytes;
 use futures::TryStreamExt;
 
@@ -25,6 +25,66 @@ impl From<iroh::client::tags::TagInfo> for TagInfo {
     }
 }
 
+impl From<(iroh::blobs::Tag, iroh::blobs::HashAndFormat)> for TagInfo {
+    fn from((name, value): (iroh::blobs::Tag, iroh::blobs::HashAndFormat)) -> Self {
+        TagInfo {
+            name: name.0.to_vec(),
+            format: value.format.into(),
+            hash: Arc::new(value.hash.into()),
+        }
+    }
+}
+
+/// A tag, a named, persistent pointer to a blob or hash sequence.
+///
+/// Data that is referenced by a tag is protected from garbage collection.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Object)]
+#[uniffi::export(Display)]
+pub struct Tag(pub(crate) iroh::blobs::Tag);
+
+impl From<iroh::blobs::Tag> for Tag {
+    fn from(t: iroh::blobs::Tag) -> Self {
+        Tag(t)
+    }
+}
+
+/// Shows the name of the tag, so it can be recreated with [`Tag::from_string`].
+///
+/// Names that are not valid UTF-8 are shown hex encoded, use [`Tag::to_bytes`] for those.
+impl std::fmt::Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match std::str::from_utf8(&self.0 .0) {
+            Ok(name) => f.write_str(name),
+            Err(_) => f.write_str(&data_encoding::HEXLOWER.encode(&self.0 .0)),
+        }
+    }
+}
+
+#[uniffi::export]
+impl Tag {
+    /// Create a tag from its raw bytes.
+    #[uniffi::constructor]
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Tag(iroh::blobs::Tag(Bytes::from(bytes)))
+    }
+
+    /// Create a tag from a string, e.g. `user/1234/avatar`.
+    #[uniffi::constructor]
+    pub fn from_string(s: String) -> Self {
+        Tag(iroh::blobs::Tag::from(s))
+    }
+
+    /// The raw bytes of the tag.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0 .0.to_vec()
+    }
+
+    /// Returns true if the tags have the same value
+    pub fn equal(&self, other: &Tag) -> bool {
+        *self == *other
+    }
+}
+
 /// Iroh tags client.
 #[derive(uniffi::Object)]
 pub struct Tags {
@@ -43,6 +103,50 @@ impl Tags {
     fn client(&self) -> &iroh::client::Iroh {
         self.node.inner_client()
     }
+
+    /// The tag index of nodes spawned by this library, RPC clients have to list all tags.
+    fn index(&self) -> Option<&TagIndex> {
+        match &self.node {
+            Iroh::Fs(_, _, state) | Iroh::Memory(_, _, state) => Some(&state.tags),
+            Iroh::Client(_) => None,
+        }
+    }
+
+    /// Rename a tag with separate requests, for clients that can not lock the tags of the node.
+    async fn rename_separately(
+        &self,
+        from: iroh::blobs::Tag,
+        to: iroh::blobs::Tag,
+    ) -> Result<(), IrohError> {
+        let (from, to) = (Arc::new(Tag(from)), Arc::new(Tag(to)));
+        if self.get(to.clone()).await?.is_some() {
+            return Err(anyhow::anyhow!("tag {} already exists", to).into());
+        }
+        let info = self
+            .get(from.clone())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("tag {} does not exist", from))?;
+        self.set(to, info.hash, info.format).await?;
+        self.client().tags().delete(from.0.clone()).await?;
+        Ok(())
+    }
+
+    /// List all tags whose name matches `filter`.
+    async fn list_filtered(
+        &self,
+        filter: impl Fn(&[u8]) -> bool,
+    ) -> Result<Vec<TagInfo>, IrohError> {
+        let tags = self
+            .client()
+            .tags()
+            .list()
+            .await?
+            .try_filter(|tag| futures::future::ready(filter(&tag.name.0)))
+            .map_ok(TagInfo::from)
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(tags)
+    }
 }
 
 #[uniffi::export]
@@ -50,7 +154,7 @@ impl Tags {
     /// List all tags
     ///
     /// Note: this allocates for each `ListTagsResponse`, if you have many `Tags`s this may be a prohibitively large list.
-    /// Please file an [issue](https://github.com/n0-computer/iroh-ffi/issues/new) if you run into this issue
+    /// Use [`Self::list_iter`] to enumerate the tags in batches instead.
     #[uniffi::method(async_runtime = "tokio")]
     pub async fn list(&self) -> Result<Vec<TagInfo>, IrohError> {
         let tags = self
@@ -64,11 +168,271 @@ impl Tags {
         Ok(tags)
     }
 
+    /// Iterate over all tags.
+    ///
+    /// Tags are fetched lazily, in batches, via [`TagListIterator::next_batch`].
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn list_iter(&self) -> Result<Arc<TagListIterator>, IrohError> {
+        let stream = self.client().tags().list().await?.map_ok(TagInfo::from);
+        Ok(Arc::new(TagListIterator(BatchStream::new(stream))))
+    }
+
+    /// List all tags whose name starts with `prefix`, ordered by name.
+    ///
+    /// Note: clients created with [`Iroh::client`] filter the tags on their side, so this
+    /// still iterates over all tags for them.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn list_prefix(&self, prefix: Vec<u8>) -> Result<Vec<TagInfo>, IrohError> {
+        if let Some(index) = self.index() {
+            let tags = index.prefix(&prefix).await;
+            return Ok(tags.into_iter().map(TagInfo::from).collect());
+        }
+        self.list_filtered(|name| name.starts_with(&prefix)).await
+    }
+
+    /// List all tags with a name in the range from `start` (inclusive) to `end` (exclusive),
+    /// ordered by name. If `end` is not set, all tags from `start` on are listed.
+    ///
+    /// Note: clients created with [`Iroh::client`] filter the tags on their side, so this
+    /// still iterates over all tags for them.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn list_range(
+        &self,
+        start: Vec<u8>,
+        end: Option<Vec<u8>>,
+    ) -> Result<Vec<TagInfo>, IrohError> {
+        if let Some(index) = self.index() {
+            let range = (
+                Bound::Included(iroh::blobs::Tag(start.into())),
+                end.map_or(Bound::Unbounded, |end| {
+                    Bound::Excluded(iroh::blobs::Tag(end.into()))
+                }),
+            );
+            let tags = index.range(range).await;
+            return Ok(tags.into_iter().map(TagInfo::from).collect());
+        }
+        self.list_filtered(|name| {
+            name >= start.as_slice() && end.as_ref().map_or(true, |end| name < end.as_slice())
+        })
+        .await
+    }
+
+    /// Get the tag with the given name, if it exists.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn get(&self, name: Arc<Tag>) -> Result<Option<TagInfo>, IrohError> {
+        if let Some(index) = self.index() {
+            let value = index.get(&name.0).await;
+            return Ok(value.map(|value| (name.0.clone(), value).into()));
+        }
+        let mut tags = self.client().tags().list().await?;
+        while let Some(tag) = tags.try_next().await? {
+            if tag.name == name.0 {
+                return Ok(Some(tag.into()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Set the tag `name` to point to `hash`, creating it if it does not exist.
+    ///
+    /// An existing tag is updated atomically, so it always points either to the old or to
+    /// the new content.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn set(
+        &self,
+        name: Arc<Tag>,
+        hash: Arc<Hash>,
+        format: BlobFormat,
+    ) -> Result<(), IrohError> {
+        let content = iroh::blobs::HashAndFormat {
+            hash: hash.0,
+            format: format.into(),
+        };
+        // tags can only be set on a batch, protect the content until the tag is set
+        let batch = self.client().blobs().batch().await?;
+        let temp_tag = batch.temp_tag(content).await?;
+        batch.persist_to(temp_tag, name.0.clone()).await?;
+        Ok(())
+    }
+
+    /// Rename the tag `from` to `to`.
+    ///
+    /// Fails if there is no tag named `from`, or if there already is a tag named `to`.
+    /// The new tag is created before the old one is removed, so the content stays
+    /// protected throughout.
+    ///
+    /// The node does not write other tags while renaming. Clients created with
+    /// [`Iroh::client`] rename in separate requests instead, so this is not atomic for
+    /// them: a concurrent writer may change either tag in between, and if removing `from`
+    /// fails, both tags are left.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn rename(&self, from: Arc<Tag>, to: Arc<Tag>) -> Result<(), IrohError> {
+        let (from, to) = (from.0.clone(), to.0.clone());
+        match &self.node {
+            Iroh::Fs(_, store, _) => store.rename_tag(from, to).await?,
+            Iroh::Memory(_, store, _) => store.rename_tag(from, to).await?,
+            Iroh::Client(_) => self.rename_separately(from, to).await?,
+        }
+        Ok(())
+    }
+
     /// Delete a tag
     #[uniffi::method(async_runtime = "tokio")]
-    pub async fn delete(&self, name: Vec<u8>) -> Result<(), IrohError> {
-        let tag = iroh::blobs::Tag(Bytes::from(name));
-        self.client().tags().delete(tag).await?;
+    pub async fn delete(&self, name: Arc<Tag>) -> Result<(), IrohError> {
+        self.client().tags().delete(name.0.clone()).await?;
         Ok(())
     }
 }
+
+/// Iterator over tags, created via [`Tags::list_iter`].
+#[derive(uniffi::Object)]
+pub struct TagListIterator(BatchStream<TagInfo>);
+
+#[uniffi::export]
+impl TagListIterator {
+    /// Get the next `n` tags.
+    ///
+    /// Returns an empty list once all tags have been returned.
+    #[uniffi::method(async_runtime = "tokio")]
+    pub async fn next_batch(&self, n: u32) -> Result<Vec<TagInfo>, IrohError> {
+        self.0.next_batch(n).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag() {
+        let tag = Tag::from_string("user/1234/avatar".to_string());
+        assert_eq!(tag.to_bytes(), b"user/1234/avatar");
+        assert!(tag.equal(&Tag::from_bytes(b"user/1234/avatar".to_vec())));
+        assert_eq!(tag.to_string(), "user/1234/avatar");
+        assert_eq!(Tag::from_string(tag.to_string()), tag);
+        assert_eq!(Tag::from_bytes(vec![0xff, 0x01]).to_string(), "ff01");
+    }
+
+    #[tokio::test]
+    async fn test_tags_set_rename() {
+        let node = Iroh::memory().await.unwrap();
+        let tags = node.tags();
+        let hash = node
+            .blobs()
+            .add_bytes(b"avatar".to_vec())
+            .await
+            .unwrap()
+            .hash;
+        let name = |s: &str| Arc::new(Tag::from_string(s.to_string()));
+
+        for s in ["user/1/avatar", "user/2/avatar", "user/3/avatar", "other"] {
+            tags.set(name(s), hash.clone(), BlobFormat::Raw)
+                .await
+                .unwrap();
+        }
+        let info = tags.get(name("user/1/avatar")).await.unwrap().unwrap();
+        assert_eq!(*info.hash, *hash);
+        assert!(tags.get(name("user/4/avatar")).await.unwrap().is_none());
+
+        let names = |infos: Vec<TagInfo>| {
+            infos
+                .into_iter()
+                .map(|i| String::from_utf8(i.name).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let user = tags.list_prefix(b"user/".to_vec()).await.unwrap();
+        assert_eq!(
+            names(user),
+            ["user/1/avatar", "user/2/avatar", "user/3/avatar"]
+        );
+        let range = tags
+            .list_range(b"user/2".to_vec(), Some(b"user/3".to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(names(range), ["user/2/avatar"]);
+
+        // point an existing tag to new content
+        let new_hash = node.blobs().add_bytes(b"new".to_vec()).await.unwrap().hash;
+        tags.set(name("user/1/avatar"), new_hash.clone(), BlobFormat::Raw)
+            .await
+            .unwrap();
+        let info = tags.get(name("user/1/avatar")).await.unwrap().unwrap();
+        assert_eq!(*info.hash, *new_hash);
+
+        tags.rename(name("user/1/avatar"), name("user/5/avatar"))
+            .await
+            .unwrap();
+        assert!(tags.get(name("user/1/avatar")).await.unwrap().is_none());
+        assert!(tags.get(name("user/5/avatar")).await.unwrap().is_some());
+        assert!(tags
+            .rename(name("user/2/avatar"), name("user/5/avatar"))
+            .await
+            .is_err());
+        assert!(tags
+            .rename(name("missing"), name("user/6/avatar"))
+            .await
+            .is_err());
+        let renamed = node.blobs().tags_for(new_hash.clone()).await.unwrap();
+        assert!(renamed.contains(&b"user/5/avatar".to_vec()));
+        assert!(!renamed.contains(&b"user/1/avatar".to_vec()));
+
+        // concurrent renames to the same name do not overwrite each other
+        let sources = (0..10)
+            .map(|i| name(&format!("source/{i}")))
+            .collect::<Vec<_>>();
+        for source in &sources {
+            tags.set(source.clone(), hash.clone(), BlobFormat::Raw)
+                .await
+                .unwrap();
+        }
+        let renames = sources.iter().map(|source| {
+            let tags = node.tags();
+            let source = source.clone();
+            async move { tags.rename(source, name("target")).await }
+        });
+        let results = futures::future::join_all(renames).await;
+        assert_eq!(1, results.iter().filter(|res| res.is_ok()).count());
+        let left = tags.list_prefix(b"source/".to_vec()).await.unwrap();
+        assert_eq!(9, left.len());
+
+        tags.delete(name("target")).await.unwrap();
+        assert!(tags.get(name("target")).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tags_client() {
+        let opts = crate::NodeOptions {
+            enable_rpc: true,
+            ..Default::default()
+        };
+        let node = Iroh::memory_with_options(opts).await.unwrap();
+        let client = Iroh::client(Some(node.node().my_rpc_addr().unwrap()))
+            .await
+            .unwrap();
+        let hash = node
+            .blobs()
+            .add_bytes_named(b"data".to_vec(), "a/1".to_string())
+            .await
+            .unwrap()
+            .hash;
+        let name = |s: &str| Arc::new(Tag::from_string(s.to_string()));
+        for s in ["a/2", "b/1"] {
+            node.tags()
+                .set(name(s), hash.clone(), BlobFormat::Raw)
+                .await
+                .unwrap();
+        }
+
+        // the node looks tags up in its index, the client lists them, with the same results
+        let names = |infos: Vec<TagInfo>| infos.into_iter().map(|i| i.name).collect::<Vec<_>>();
+        for tags in [node.tags(), client.tags()] {
+            let prefix = tags.list_prefix(b"a/".to_vec()).await.unwrap();
+            assert_eq!(names(prefix), [b"a/1".to_vec(), b"a/2".to_vec()]);
+            let range = tags.list_range(b"a/2".to_vec(), None).await.unwrap();
+            assert_eq!(names(range), [b"a/2".to_vec(), b"b/1".to_vec()]);
+            let info = tags.get(name("b/1")).await.unwrap().unwrap();
+            assert_eq!(*info.hash, *hash);
+            assert!(tags.get(name("b/2")).await.unwrap().is_none());
+        }
+    }
+}
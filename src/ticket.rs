@@ -1,7 +1,7 @@
 use std::str::FromStr;
 use std::sync::Arc;
 
-use crate::blob::{BlobDownloadOptions, BlobFormat, Hash};
+use crate::blob::{BlobDownloadOptions, BlobFormat, DownloadMode, Hash};
 use crate::doc::NodeAddr;
 use crate::error::IrohError;
 
@@ -55,11 +55,17 @@ impl BlobTicket {
 
     /// Convert this ticket into input parameters for a call to blobs_download
     pub fn as_download_options(&self) -> Arc<BlobDownloadOptions> {
+        self.as_download_options_with_mode(DownloadMode::Direct)
+    }
+
+    /// Convert this ticket into input parameters for a call to blobs_download, using the
+    /// given download mode.
+    pub fn as_download_options_with_mode(&self, mode: DownloadMode) -> Arc<BlobDownloadOptions> {
         let r: BlobDownloadOptions = iroh::client::blobs::DownloadOptions {
             format: self.0.format(),
             nodes: vec![self.0.node_addr().clone()],
             tag: iroh::blobs::util::SetTagOption::Auto,
-            mode: iroh::client::blobs::DownloadMode::Direct,
+            mode: mode.into(),
         }
         .into();
         Arc::new(r)